@@ -1,6 +1,9 @@
+#[cfg(feature = "gui")]
 use macroquad::prelude::*;
 
-pub const SPEED:f32 = 5.0;
+use crate::conflict::Cells;
+use crate::scenario::VehicleSpec;
+use serde::Deserialize;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Turn {
@@ -9,7 +12,17 @@ pub enum Turn {
     Front,
 }
 
+impl Turn {
+    pub const ALL: [Turn; 3] = [Turn::Left, Turn::Front, Turn::Right];
 
+    pub fn index(self) -> usize {
+        match self {
+            Turn::Left => 0,
+            Turn::Front => 1,
+            Turn::Right => 2,
+        }
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Col {
@@ -18,7 +31,56 @@ pub enum Col {
     Gold,
 }
 
-#[derive(Clone, Copy, PartialEq)]
+/// The kind of vehicle: sets its size and performance (see
+/// `Scenario::spec`) and how it is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VehicleClass {
+    Car,
+    Truck,
+    Bus,
+    Motorcycle,
+    // Never part of the random mix; preempts the signals when it comes.
+    Emergency,
+    // Rides in the bike lane; spawned on its own, never by the mix.
+    Bicycle,
+}
+
+impl VehicleClass {
+    pub const ALL: [VehicleClass; 6] = [
+        VehicleClass::Car,
+        VehicleClass::Truck,
+        VehicleClass::Bus,
+        VehicleClass::Motorcycle,
+        VehicleClass::Emergency,
+        VehicleClass::Bicycle,
+    ];
+
+    pub fn index(self) -> usize {
+        match self {
+            VehicleClass::Car => 0,
+            VehicleClass::Truck => 1,
+            VehicleClass::Bus => 2,
+            VehicleClass::Motorcycle => 3,
+            VehicleClass::Emergency => 4,
+            VehicleClass::Bicycle => 5,
+        }
+    }
+
+    pub fn key(self) -> &'static str {
+        match self {
+            VehicleClass::Car => "car",
+            VehicleClass::Truck => "truck",
+            VehicleClass::Bus => "bus",
+            VehicleClass::Motorcycle => "motorcycle",
+            VehicleClass::Emergency => "emergency",
+            VehicleClass::Bicycle => "bicycle",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Direction {
     North,
     South,
@@ -26,126 +88,618 @@ pub enum Direction {
     West,
 }
 
+impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::North,
+        Direction::South,
+        Direction::East,
+        Direction::West,
+    ];
+
+    pub fn index(self) -> usize {
+        match self {
+            Direction::North => 0,
+            Direction::South => 1,
+            Direction::East => 2,
+            Direction::West => 3,
+        }
+    }
+
+    pub fn vector(self) -> (f32, f32) {
+        match self {
+            Direction::North => (0.0, -1.0),
+            Direction::South => (0.0, 1.0),
+            Direction::East => (1.0, 0.0),
+            Direction::West => (-1.0, 0.0),
+        }
+    }
+
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::North => Direction::South,
+            Direction::South => Direction::North,
+            Direction::East => Direction::West,
+            Direction::West => Direction::East,
+        }
+    }
+
+    pub fn left(self) -> Direction {
+        match self {
+            Direction::North => Direction::West,
+            Direction::South => Direction::East,
+            Direction::East => Direction::North,
+            Direction::West => Direction::South,
+        }
+    }
+
+    pub fn right(self) -> Direction {
+        self.left().opposite()
+    }
+
+    pub fn turned(self, turn: Turn) -> Direction {
+        match turn {
+            Turn::Left => self.left(),
+            Turn::Right => self.right(),
+            Turn::Front => self,
+        }
+    }
+
+    // Position along this direction of travel.
+    pub fn along(self, x: f32, y: f32) -> f32 {
+        let (vx, vy) = self.vector();
+        x * vx + y * vy
+    }
+
+    // Position across this direction of travel, increasing to the right.
+    pub fn lateral(self, x: f32, y: f32) -> f32 {
+        self.right().along(x, y)
+    }
+
+    // Screen angle of travel in radians (y points down, so north is -π/2).
+    pub fn angle(self) -> f32 {
+        let (vx, vy) = self.vector();
+        vy.atan2(vx)
+    }
+}
+
+/// A quarter circle taking a turning car from its approach lane onto its
+/// exit lane.
+#[derive(Debug, Clone, Copy)]
+pub struct TurnArc {
+    pub cx: f32,
+    pub cy: f32,
+    pub radius: f32,
+    // Angle of the start point as seen from the centre, and the signed sweep
+    // to the end point.
+    pub start: f32,
+    pub sweep: f32,
+}
+
+impl TurnArc {
+    /// The arc of the given radius from travelling in `from` to travelling
+    /// in `to`, ending `radius` past the point `(x, y)` where the two lane
+    /// centre lines cross.
+    pub fn new(from: Direction, to: Direction, x: f32, y: f32, radius: f32) -> Self {
+        let (vx, vy) = from.vector();
+        let (wx, wy) = to.vector();
+        Self {
+            cx: x + (wx - vx) * radius,
+            cy: y + (wy - vy) * radius,
+            radius,
+            start: (-wy).atan2(-wx),
+            // Clockwise on screen for right turns, anticlockwise for left.
+            sweep: (vx * wy - vy * wx) * std::f32::consts::FRAC_PI_2,
+        }
+    }
+
+    pub fn length(&self) -> f32 {
+        self.radius * self.sweep.abs()
+    }
+
+    /// Position and heading angle `distance` along the arc.
+    pub fn at(&self, distance: f32) -> (f32, f32, f32) {
+        let theta = self.start + self.sweep.signum() * distance / self.radius;
+        let heading = theta + self.sweep.signum() * std::f32::consts::FRAC_PI_2;
+        (
+            self.cx + self.radius * theta.cos(),
+            self.cy + self.radius * theta.sin(),
+            heading,
+        )
+    }
+
+    /// A point on the whole path: the arc, extended straight back along the
+    /// approach before it and straight on along the exit after it.
+    pub fn point(&self, distance: f32) -> (f32, f32) {
+        let end = distance.clamp(0.0, self.length());
+        let (x, y, heading) = self.at(end);
+        let beyond = distance - end;
+        (x + beyond * heading.cos(), y + beyond * heading.sin())
+    }
+
+    /// Centre and angle of a body `length` long whose front is `front` along
+    /// the path and whose rear follows the path behind it, so long vehicles
+    /// cut the corner the way their rear wheels do.
+    pub fn pose(&self, front: f32, length: f32) -> (f32, f32, f32) {
+        let (fx, fy) = self.point(front);
+        let (rx, ry) = self.point(front - length);
+        ((fx + rx) / 2.0, (fy + ry) / 2.0, (fy - ry).atan2(fx - rx))
+    }
+}
+
+/// A move across into the next lane along a smooth S, `length` of road
+/// long. As on a turn, the front follows the S and the rear trails it.
+#[derive(Debug, Clone, Copy)]
+pub struct LaneChange {
+    // Where the car started, across its heading from the centre line of the
+    // lane it is moving into.
+    pub offset: f32,
+    pub length: f32,
+    // Distance the front has driven since the change began.
+    pub done: f32,
+}
+
+impl LaneChange {
+    // Offset of the S from the new lane's centre line `distance` into it.
+    fn offset_at(&self, distance: f32) -> f32 {
+        let phase = std::f32::consts::PI * (distance / self.length).clamp(0.0, 1.0);
+        self.offset * (1.0 + phase.cos()) / 2.0
+    }
+
+    // Offset of the middle of a body `length` long with its front `front`
+    // into the change, and the angle of the body off the lane.
+    fn pose(&self, front: f32, length: f32) -> (f32, f32) {
+        let (ahead, behind) = (self.offset_at(front), self.offset_at(front - length));
+        ((ahead + behind) / 2.0, (ahead - behind).atan2(length))
+    }
+}
+
 pub struct Car {
+    pub id: u32,
+    pub class: VehicleClass,
     pub direction: Direction,
+    pub heading: Direction,
+    // Index of the lane, counted from the centre line out: the approach
+    // lane until the car turns, and the one it is moving into while it
+    // changes lanes.
+    pub lane: usize,
+    pub lane_change: Option<LaneChange>,
     pub x: f32,
     pub y: f32,
-    pub dx: f32,
-    pub dy: f32,
+    // Angle the body points in; follows `heading` except while turning.
+    pub angle: f32,
+    // The turn being driven and how far along it the front of the car is.
+    pub arc: Option<TurnArc>,
+    pub on_arc: f32,
+    // Set while a cyclist on a two-stage left waits in the queue box, until
+    // it rides off across the box.
+    pub queued: bool,
+    // Cells of the box the movement sweeps, before and after the turn
+    // starts (see `ConflictZone::path`).
+    pub path: (Cells, Cells),
+    // Simulation time the car entered, and at an all-way stop the time it
+    // came to a full stop at its line.
+    pub spawned_at: f64,
+    pub arrived: Option<f64>,
     pub check_move: bool,
     pub check: bool,
-    pub color: Color,
+    pub col: Col,
     pub turn: Turn,
+    pub length: f32,
+    pub width: f32,
+    // Current speed and the driver's parameters for the Intelligent Driver
+    // Model.
+    pub speed: f32,
+    pub max_speed: f32,
+    pub accel: f32,
+    pub decel: f32,
+    pub headway: f32,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightState {
+    Red,
+    // Shown between red and green where the scenario configures it.
+    RedAmber,
+    Green,
+    Amber,
+    // Signals out of service: proceed with caution.
+    Flashing,
+}
+
+/// A signal with one arrow head per turn, indexed by `Turn::index`.
 pub struct TrafficLight {
     pub direction: Direction,
     pub x: f32,
     pub y: f32,
-    pub green: bool,
+    pub heads: [LightState; 3],
+    // Simulation time each head's current state began.
+    pub since: [f64; 3],
 }
 
 impl Car {
+    // `start` is where the centre of the car enters.
     pub fn new(
+        id: u32,
+        class: VehicleClass,
         direction: Direction,
-        x: f32,
-        y: f32,
-        dx: f32,
-        dy: f32,
-        check_move: bool,
-        color: Color,
+        (x, y): (f32, f32),
+        col: Col,
         turn: Turn,
+        spec: &VehicleSpec,
     ) -> Self {
         Self {
+            id,
+            class,
             direction,
+            heading: direction,
+            lane: 0,
+            lane_change: None,
             x,
             y,
-            dx,
-            dy,
-            check_move,
+            angle: direction.angle(),
+            arc: None,
+            on_arc: 0.0,
+            queued: false,
+            path: (0, 0),
+            spawned_at: 0.0,
+            arrived: None,
+            check_move: true,
             check: false,
-            color,
+            col,
             turn,
+            length: spec.length,
+            width: spec.width,
+            speed: spec.speed,
+            max_speed: spec.speed,
+            accel: spec.accel,
+            decel: spec.decel,
+            headway: spec.headway,
         }
     }
 
-    pub fn update(&mut self) {
-        self.check_move = true;
-        self.x += self.dx;
-        self.y += self.dy;
-    }
-
-    pub fn update_direction(&mut self) {
-        match self.turn {
-            Turn::Left => match self.direction {
-                Direction::North => {
-                    self.dx = -SPEED;
-                    self.dy = 0.0;
-                },
-                Direction::South => {
-                    self.dx = SPEED;
-                    self.dy = 0.0;
-                },
-                Direction::East => {
-                    self.dx = 0.0;
-                    self.dy = -SPEED;
-                },
-                Direction::West => {
-                    self.dx = 0.0;
-                    self.dy = SPEED;
-                },
-            },
-            Turn::Right => match self.direction {
-                Direction::North => {
-                    self.dx = SPEED;
-                    self.dy = 0.0;
-                },
-                Direction::South => {
-                    self.dx = -SPEED;
-                    self.dy = 0.0;
-                },
-                Direction::East => {
-                    self.dx = 0.0;
-                    self.dy = SPEED;
-                },
-                Direction::West => {
-                    self.dx = 0.0;
-                    self.dy = -SPEED;
-                },
-            },
-            _ => {}
+    // Distance needed to come to rest from the current speed at comfortable
+    // braking.
+    pub fn stopping_distance(&self) -> f32 {
+        self.speed * self.speed / (2.0 * self.decel)
+    }
+
+    /// IDM acceleration towards the desired speed, or behind an obstacle
+    /// `gap` pixels ahead that the car is closing on at `closing` pixels per
+    /// second.
+    pub fn acceleration(&self, obstacle: Option<(f32, f32)>) -> f32 {
+        let free = 1.0 - (self.speed / self.max_speed).powi(4);
+        let Some((gap, closing)) = obstacle else {
+            return self.accel * free;
+        };
+        let desired = self.speed * self.headway
+            + self.speed * closing / (2.0 * (self.accel * self.decel).sqrt());
+        self.accel * (free - (desired.max(0.0) / gap.max(1.0)).powi(2))
+    }
+
+    pub fn along(&self) -> f32 {
+        self.heading.along(self.x, self.y)
+    }
+
+    /// Corners of the body: front right, front left, rear left, rear right.
+    pub fn corners(&self) -> [(f32, f32); 4] {
+        body_corners(self.x, self.y, self.angle, self.length, self.width)
+    }
+
+    pub fn lateral(&self) -> f32 {
+        self.heading.lateral(self.x, self.y)
+    }
+
+    /// Middle of the back of the body.
+    pub fn rear(&self) -> (f32, f32) {
+        let (s, c) = self.angle.sin_cos();
+        (
+            self.x - c * self.length / 2.0,
+            self.y - s * self.length / 2.0,
+        )
+    }
+
+    pub fn advance(&mut self, distance: f32) {
+        self.check_move = distance > 0.0;
+        if let Some(arc) = self.arc {
+            self.on_arc += distance;
+            (self.x, self.y, self.angle) = arc.pose(self.on_arc, self.length);
+            // Straight on the exit lane once the rear is off the arc.
+            if self.on_arc - self.length >= arc.length() {
+                self.arc = None;
+                self.angle = self.heading.angle();
+            }
+        } else {
+            let (vx, vy) = self.heading.vector();
+            let (rx, ry) = self.heading.right().vector();
+            // Drift across towards the new lane, angled along the S.
+            let mut side = 0.0;
+            if let Some(change) = &mut self.lane_change {
+                let (before, _) = change.pose(change.done, self.length);
+                change.done += distance;
+                let (after, angle) = change.pose(change.done, self.length);
+                side = after - before;
+                self.angle = self.heading.angle() + angle;
+                if change.done - self.length >= change.length {
+                    self.lane_change = None;
+                    self.angle = self.heading.angle();
+                }
+            }
+            self.x += vx * distance + rx * side;
+            self.y += vy * distance + ry * side;
         }
     }
 
-    pub fn draw(&self) {
-        draw_rectangle(self.x, self.y, 50.0, 50.0, self.color);
-        draw_rectangle_lines(self.x, self.y, 50.0, 50.0, 2.0, WHITE);
+    /// Starts moving across into `lane`, whose centre line is `offset` from
+    /// the car's, over `length` of road.
+    pub fn change_lane(&mut self, lane: usize, offset: f32, length: f32) {
+        self.lane = lane;
+        self.lane_change = Some(LaneChange {
+            offset: -offset,
+            length,
+            done: 0.0,
+        });
     }
+
+    /// Whether this is a cyclist turning left, which it does in two stages:
+    /// straight over to the far corner and right into the queue box there,
+    /// then across with the traffic on the street it turns into.
+    pub fn two_stage(&self) -> bool {
+        self.class == VehicleClass::Bicycle && self.turn == Turn::Left
+    }
+
+    /// The signal head the vehicle goes by at its stop line; a two-stage
+    /// left starts on the through one.
+    pub fn signal_turn(&self) -> Turn {
+        if self.two_stage() {
+            Turn::Front
+        } else {
+            self.turn
+        }
+    }
+
+    /// The legs whose crosswalks the vehicle drives over, coming in and
+    /// going out. A two-stage left crosses only the one it comes in over on
+    /// its way to the queue box, and only the one it goes out over from it.
+    pub fn crosswalk_legs(&self) -> [Option<Direction>; 2] {
+        let entry = Some(self.direction.opposite());
+        if !self.two_stage() {
+            [entry, Some(self.direction.turned(self.turn))]
+        } else if self.queued || self.heading == self.direction.left() {
+            [None, Some(self.direction.left())]
+        } else {
+            [entry, None]
+        }
+    }
+
+    /// Switches to the exit lane as the front reaches the start of the turn,
+    /// driving onto it along `arc` if given.
+    pub fn start_turn(&mut self, arc: Option<TurnArc>) {
+        self.heading = if self.two_stage() {
+            self.direction.right()
+        } else {
+            self.direction.turned(self.turn)
+        };
+        self.arc = arc;
+        self.on_arc = 0.0;
+        if arc.is_none() {
+            self.angle = self.heading.angle();
+        }
+    }
+
+    /// Brings a cyclist on a two-stage left to a halt at `(x, y)` in the
+    /// queue box, turned to face across the street it is turning into.
+    pub fn queue(&mut self, (x, y): (f32, f32)) {
+        self.x = x;
+        self.y = y;
+        self.heading = self.direction.left();
+        self.angle = self.heading.angle();
+        self.speed = 0.0;
+        self.queued = true;
+    }
+}
+
+/// Corners of a `length` by `width` body centred on `x`/`y` and pointing
+/// along `angle`: front right, front left, rear left, rear right.
+pub fn body_corners(x: f32, y: f32, angle: f32, length: f32, width: f32) -> [(f32, f32); 4] {
+    let (s, c) = angle.sin_cos();
+    let (fx, fy) = (c * length / 2.0, s * length / 2.0);
+    // To the right of the direction of travel.
+    let (rx, ry) = (-s * width / 2.0, c * width / 2.0);
+    [
+        (x + fx + rx, y + fy + ry),
+        (x + fx - rx, y + fy - ry),
+        (x - fx - rx, y - fy - ry),
+        (x - fx + rx, y - fy + ry),
+    ]
 }
 
 impl TrafficLight {
-    pub fn new(direction: Direction, x: f32, y: f32, start_green: bool) -> Self {
+    pub fn new(direction: Direction, x: f32, y: f32, state: LightState) -> Self {
         Self {
             direction,
             x,
             y,
-            green: start_green,
+            heads: [state; 3],
+            since: [0.0; 3],
         }
     }
-    pub fn update(&mut self) {
-        if !self.green {
-            self.green = !self.green;
+
+    pub fn state(&self, turn: Turn) -> LightState {
+        self.heads[turn.index()]
+    }
+
+    pub fn set(&mut self, turn: Turn, state: LightState, now: f64) {
+        let i = turn.index();
+        if self.heads[i] != state {
+            self.heads[i] = state;
+            self.since[i] = now;
+        }
+    }
+
+    pub fn set_all(&mut self, state: LightState, now: f64) {
+        for turn in Turn::ALL {
+            self.set(turn, state, now);
+        }
+    }
+
+    pub fn is_green(&self, turn: Turn) -> bool {
+        self.state(turn) == LightState::Green
+    }
+}
+
+#[cfg(feature = "gui")]
+impl Car {
+    pub fn draw(&self) {
+        let color = Color::from(self.col);
+        let glass = Color::new(0.1, 0.1, 0.15, 0.85);
+        match self.class {
+            VehicleClass::Car => {
+                self.panel(0.0, 1.0, 1.0, color);
+                self.panel(0.15, 0.18, 0.8, glass);
+            }
+            // Cab in the turn colour pulling a grey box.
+            VehicleClass::Truck => {
+                self.panel(-0.14, 0.7, 1.0, Color::new(0.75, 0.75, 0.78, 1.0));
+                self.panel(0.36, 0.26, 0.92, color);
+                self.panel(0.42, 0.08, 0.8, glass);
+            }
+            // Windows all along both sides.
+            VehicleClass::Bus => {
+                self.panel(0.0, 1.0, 1.0, color);
+                self.panel(-0.04, 0.8, 0.86, glass);
+                self.panel(-0.04, 0.8, 0.56, color);
+                self.panel(0.45, 0.05, 0.86, glass);
+            }
+            // A rider's helmet on a narrow frame.
+            VehicleClass::Motorcycle => {
+                self.panel(0.0, 1.0, 0.6, color);
+                draw_circle(self.x, self.y, self.width * 0.45, DARKGRAY);
+            }
+            // White with a band in the turn colour and a light bar
+            // flashing red and blue.
+            VehicleClass::Emergency => {
+                self.panel(0.0, 1.0, 1.0, WHITE);
+                self.panel(-0.25, 0.3, 1.0, color);
+                self.panel(0.2, 0.15, 0.8, glass);
+                let bar = if get_time().fract() < 0.5 { RED } else { BLUE };
+                self.panel(0.0, 0.12, 0.9, bar);
+            }
+            // Two wheels and a rider in the turn colour.
+            VehicleClass::Bicycle => {
+                self.panel(0.3, 0.4, 0.35, DARKGRAY);
+                self.panel(-0.3, 0.4, 0.35, DARKGRAY);
+                draw_circle(self.x, self.y, self.width * 0.5, color);
+            }
+        }
+
+        // Headlights mark the front.
+        let corners = self.corners();
+        if matches!(self.class, VehicleClass::Motorcycle | VehicleClass::Bicycle) {
+            let (x, y) = (
+                (corners[0].0 + corners[1].0) / 2.0,
+                (corners[0].1 + corners[1].1) / 2.0,
+            );
+            draw_circle(x, y, 2.0, YELLOW);
+        } else {
+            for &(x, y) in &corners[..2] {
+                let (x, y) = (x + (self.x - x) * 0.15, y + (self.y - y) * 0.15);
+                draw_circle(x, y, 2.5, YELLOW);
+            }
+        }
+
+        // Indicators blink on the side it is moving over to.
+        if let Some(change) = &self.lane_change
+            && get_time().fract() < 0.5
+        {
+            let side = if change.offset < 0.0 { [0, 3] } else { [1, 2] };
+            for i in side {
+                let (x, y) = corners[i];
+                let (x, y) = (x + (self.x - x) * 0.1, y + (self.y - y) * 0.1);
+                draw_circle(x, y, 2.5, ORANGE);
+            }
+        }
+
+        let outline = if self.class == VehicleClass::Bicycle {
+            1.0
+        } else {
+            2.0
+        };
+        for i in 0..4 {
+            let (x1, y1) = corners[i];
+            let (x2, y2) = corners[(i + 1) % 4];
+            draw_line(x1, y1, x2, y2, outline, WHITE);
         }
     }
 
+    // A rectangle on the body, centred `ahead` lengths in front of the
+    // middle; sizes are fractions of the body's.
+    fn panel(&self, ahead: f32, length: f32, width: f32, color: Color) {
+        let (s, c) = self.angle.sin_cos();
+        let params = DrawRectangleParams {
+            offset: vec2(0.5, 0.5),
+            rotation: self.angle,
+            color,
+        };
+        draw_rectangle_ex(
+            self.x + c * ahead * self.length,
+            self.y + s * ahead * self.length,
+            self.length * length,
+            self.width * width,
+            params,
+        );
+    }
+}
+
+#[cfg(feature = "gui")]
+impl TrafficLight {
     pub fn draw(&self) {
-        let color = if self.green { GREEN } else { RED };
-        draw_circle(self.x + 20.0, self.y + 20.0, 12.0, color);
-        draw_circle_lines(self.x + 20.0, self.y + 20.0, 12.0, 2.0, WHITE);
-        draw_rectangle(self.x + 15.0, self.y + 32.0, 10.0, 20.0, BROWN);
+        // Flashing amber blinks at 1 Hz on the wall clock.
+        let blink_on = get_time().fract() < 0.5;
+
+        draw_rectangle(self.x, self.y, 48.0, 50.0, BLACK);
+        draw_rectangle_lines(self.x, self.y, 48.0, 50.0, 2.0, WHITE);
+        // One column per arrow: left, straight, right.
+        for turn in Turn::ALL {
+            let (red, amber, green) = match self.state(turn) {
+                LightState::Red => (true, false, false),
+                LightState::RedAmber => (true, true, false),
+                LightState::Green => (false, false, true),
+                LightState::Amber => (false, true, false),
+                LightState::Flashing => (false, blink_on, false),
+            };
+            let cx = self.x + 8.0 + 16.0 * turn.index() as f32;
+            for (i, (lit, color)) in [(red, RED), (amber, ORANGE), (green, GREEN)]
+                .into_iter()
+                .enumerate()
+            {
+                let color = if lit {
+                    color
+                } else {
+                    Color::new(color.r * 0.25, color.g * 0.25, color.b * 0.25, 1.0)
+                };
+                draw_arrow(cx, self.y + 9.0 + 16.0 * i as f32, turn, color);
+            }
+        }
     }
 }
 
+// A 12 px arrow centred on (x, y), pointing the way `turn` goes as seen by
+// the driver facing the signal.
+#[cfg(feature = "gui")]
+fn draw_arrow(x: f32, y: f32, turn: Turn, color: Color) {
+    let (dx, dy) = match turn {
+        Turn::Left => (-1.0, 0.0),
+        Turn::Front => (0.0, -1.0),
+        Turn::Right => (1.0, 0.0),
+    };
+    let tip = vec2(x + dx * 6.0, y + dy * 6.0);
+    let base = vec2(x + dx, y + dy);
+    let side = vec2(-dy, dx) * 4.0;
+    draw_line(x - dx * 6.0, y - dy * 6.0, base.x, base.y, 2.5, color);
+    draw_triangle(tip, base + side, base - side, color);
+}
+
+#[cfg(feature = "gui")]
 impl From<Col> for Color {
     fn from(col: Col) -> Self {
         match col {
@@ -154,4 +708,4 @@ impl From<Col> for Color {
             Col::Gold => Color::from_rgba(255, 215, 0, 255),
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,186 @@
+use road_intersection::simulation::TICK;
+use road_intersection::{Control, ControllerKind, Scenario, Simulation, VehicleClass};
+use std::env;
+use std::process;
+
+fn main() {
+    let mut ticks: u64 = 10_000;
+    let mut spawn_every: u64 = 30;
+    // 0 spawns no emergency vehicles.
+    let mut emergency_every: u64 = 0;
+    // 0 spawns no pedestrians; they need crosswalks in the scenario.
+    let mut pedestrian_every: u64 = 0;
+    // 0 spawns no cyclists; they need bike lanes in the scenario.
+    let mut bicycle_every: u64 = 0;
+    let mut seed: u64 = rand::random();
+    let mut scenario = Scenario::default();
+    let mut controller: Option<ControllerKind> = None;
+    let mut control: Option<Control> = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().unwrap_or_else(|| usage());
+        let number = || value.parse::<u64>().unwrap_or_else(|_| usage());
+        match arg.as_str() {
+            "--ticks" => ticks = number(),
+            "--spawn-every" => spawn_every = number().max(1),
+            "--emergency-every" => emergency_every = number(),
+            "--pedestrian-every" => pedestrian_every = number(),
+            "--bicycle-every" => bicycle_every = number(),
+            "--seed" => seed = number(),
+            "--scenario" => {
+                scenario = Scenario::load(&value).unwrap_or_else(|err| {
+                    eprintln!("{value}: {err}");
+                    process::exit(2);
+                })
+            }
+            "--controller" => {
+                controller = Some(value.parse().unwrap_or_else(|err| {
+                    eprintln!("{err}");
+                    process::exit(2);
+                }))
+            }
+            "--control" => {
+                control = Some(value.parse().unwrap_or_else(|err| {
+                    eprintln!("{err}");
+                    process::exit(2);
+                }))
+            }
+            _ => usage(),
+        }
+    }
+
+    if let Some(kind) = controller {
+        scenario.signals.controller = kind;
+    }
+    if let Some(control) = control {
+        scenario.control = control;
+        if let Err(err) = scenario.validate() {
+            eprintln!("{err}");
+            process::exit(2);
+        }
+    }
+    let mut sim = Simulation::new(scenario, seed);
+    for tick in 0..ticks {
+        if tick % spawn_every == 0 {
+            sim.spawn_random();
+        }
+        if emergency_every > 0 && tick % emergency_every == emergency_every / 2 {
+            sim.spawn_emergency_random();
+        }
+        if pedestrian_every > 0 && tick % pedestrian_every == pedestrian_every / 3 {
+            sim.spawn_pedestrian_random();
+        }
+        if bicycle_every > 0 && tick % bicycle_every == bicycle_every * 2 / 3 {
+            sim.spawn_bicycle_random();
+        }
+        sim.step(TICK);
+    }
+
+    let stats = &sim.stats;
+    // Turning on red and preemption only happen at signals.
+    let signals = sim.scenario.control == Control::Signals;
+    println!(
+        "seed: {seed}, controller: {}, ticks: {ticks}, time: {:.1}s, spawned: {}, on road: {}",
+        sim.controller_name(),
+        sim.time,
+        stats.spawned.iter().sum::<u32>(),
+        sim.cars.len()
+    );
+    if signals {
+        println!(
+            "departed: {:?}, right turns on red: {:?}",
+            stats.departed, stats.right_turns_on_red
+        );
+    } else {
+        println!("departed: {:?}", stats.departed);
+    }
+    let classes: Vec<String> = VehicleClass::ALL
+        .iter()
+        .map(|class| {
+            let i = class.index();
+            format!(
+                "{} {}/{}",
+                class.key(),
+                stats.departed_by_class[i],
+                stats.spawned_by_class[i]
+            )
+        })
+        .collect();
+    println!("departed/spawned by class: {}", classes.join(", "));
+    if !stats.trips.is_empty() {
+        let trips = &stats.trips;
+        let mean = trips.iter().sum::<f64>() / trips.len() as f64;
+        let worst = trips.iter().copied().fold(0.0, f64::max);
+        println!("trips: mean {mean:.2}s, worst {worst:.2}s");
+    }
+    println!(
+        "lane changes: {:?}, rerouted: {:?}",
+        stats.lane_changes, stats.rerouted
+    );
+    if !stats.emergency_trips.is_empty() {
+        let trips = &stats.emergency_trips;
+        let mean = trips.iter().sum::<f64>() / trips.len() as f64;
+        let worst = trips.iter().copied().fold(0.0, f64::max);
+        if signals {
+            println!(
+                "emergency trips: {}, mean {mean:.2}s, worst {worst:.2}s; preempted {} times for {:.1}s, delay to others by approach: {:?}",
+                trips.len(),
+                stats.preemptions,
+                stats.preempted_time,
+                stats
+                    .preemption_delay
+                    .map(|delay| (delay * 10.0).round() / 10.0)
+            );
+        } else {
+            println!(
+                "emergency trips: {}, mean {mean:.2}s, worst {worst:.2}s",
+                trips.len()
+            );
+        }
+    }
+
+    if !stats.pedestrian_delays.is_empty() {
+        let delays = &stats.pedestrian_delays;
+        let mean = delays.iter().sum::<f64>() / delays.len() as f64;
+        let worst = delays.iter().copied().fold(0.0, f64::max);
+        println!(
+            "pedestrians crossed/spawned by leg: {:?}/{:?}, waiting: {}, delay mean {mean:.2}s, worst {worst:.2}s",
+            stats.pedestrians_crossed,
+            stats.pedestrians_spawned,
+            sim.pedestrians.iter().filter(|p| !p.is_crossing()).count()
+        );
+        if sim.scenario.pedestrians.scramble.is_enabled() {
+            println!(
+                "diagonals crossed/spawned: {}/{}, scrambles: {}",
+                stats.diagonals_crossed,
+                stats.diagonals_spawned,
+                sim.guard_stats().scrambles
+            );
+        }
+    }
+
+    println!("errors: {}", sim.errors.len());
+    for err in sim.errors.iter().take(10) {
+        println!("  {err}");
+    }
+
+    if !signals {
+        return;
+    }
+    let guard = sim.guard_stats();
+    println!(
+        "min-green holds: {:?}, max-green cutoffs: {:?}, max-wait overrides: {:?}, pedestrian overrides: {:?}",
+        guard.min_green_holds,
+        guard.max_green_cutoffs,
+        guard.max_wait_overrides,
+        guard.pedestrian_overrides
+    );
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: headless [--ticks N] [--spawn-every N] [--emergency-every N] [--pedestrian-every N] [--bicycle-every N] [--seed N] [--scenario FILE] [--controller NAME] [--control NAME]"
+    );
+    process::exit(2);
+}
@@ -0,0 +1,267 @@
+use crate::app::{Car, Direction, Turn, body_corners};
+use crate::scenario::Scenario;
+#[cfg(feature = "gui")]
+use macroquad::prelude::*;
+
+// Slack when testing bodies for overlap, so cars that merely touch (or
+// differ by float noise) don't count.
+const TOLERANCE: f32 = 0.5;
+
+/// A set of cells of the intersection box, one bit per cell.
+pub type Cells = u128;
+
+/// The intersection box as a grid of lane-sized cells, with a narrow one for
+/// each bike lane along the edges, numbered row by row from the north-west
+/// corner. A car reserves the cells on its path before it crosses the stop
+/// line and gives each one up once it has driven out of it, so no two cars
+/// are ever in the same cell.
+#[derive(Debug, Clone)]
+pub struct ConflictZone {
+    cells: Vec<Option<Claim>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Claim {
+    car: u32,
+    // Set once the car has been in the cell; it is released on leaving.
+    entered: bool,
+}
+
+impl ConflictZone {
+    pub fn new(scenario: &Scenario) -> Self {
+        let (columns, rows) = grid(scenario);
+        Self {
+            cells: vec![None; columns * rows],
+        }
+    }
+
+    /// The cells a vehicle's body sweeps on its movement, split where it
+    /// leaves its approach lane: those up to the start of its turn and those
+    /// along the turn and exit. A cyclist waiting in a queue box goes on
+    /// straight across along the bike lane it faces.
+    pub fn path(scenario: &Scenario, car: &Car) -> (Cells, Cells) {
+        let (direction, lane, turn) = if car.queued {
+            (car.heading, scenario.bike_lane(car.heading), Turn::Front)
+        } else {
+            (car.direction, car.lane, car.turn)
+        };
+        let (vx, vy) = direction.vector();
+        let (cx, cy) = scenario.crossing(direction, lane, turn);
+        let back = scenario.turn_radius(direction, lane, turn) + car.length / 2.0;
+        // Where the centre is as the front reaches the start of the turn.
+        let start = (cx - vx * back, cy - vy * back);
+        let (left, top, right, bottom) = scenario.intersection_box();
+        let reach = (right - left).max(bottom - top);
+        let entry = (start.0 - vx * reach, start.1 - vy * reach);
+        let approach: Vec<_> = samples(entry, start)
+            .into_iter()
+            .map(|(x, y)| (x, y, direction.angle()))
+            .collect();
+
+        // The turn itself, until the rear is off it, then straight out along
+        // the exit lane.
+        let mut turning = Vec::new();
+        let mut end = start;
+        if let Some(arc) = scenario.turn_arc(direction, lane, turn) {
+            let steps = 24;
+            let total = arc.length() + car.length;
+            for i in 0..=steps {
+                let (x, y, angle) = arc.pose(total * i as f32 / steps as f32, car.length);
+                turning.push((x, y, angle));
+                end = (x, y);
+            }
+        }
+        let exit = scenario.exit_heading(direction, lane, turn);
+        let (wx, wy) = exit.vector();
+        let leave = (end.0 + wx * reach, end.1 + wy * reach);
+        turning.extend(
+            samples(end, leave)
+                .into_iter()
+                .map(|(x, y)| (x, y, exit.angle())),
+        );
+
+        (
+            Self::swept(scenario, car, approach),
+            Self::swept(scenario, car, turning),
+        )
+    }
+
+    // Cells the body is in, by the same measure `update` uses, at any of
+    // the given positions.
+    fn swept(scenario: &Scenario, car: &Car, poses: Vec<(f32, f32, f32)>) -> Cells {
+        let (columns, rows) = grid(scenario);
+        let grid: Vec<_> = (0..columns * rows)
+            .map(|cell| Self::cell_corners(scenario, cell))
+            .collect();
+        let mut cells = 0;
+        for (x, y, angle) in poses {
+            let body = body_corners(x, y, angle, car.length, car.width);
+            for (cell, corners) in grid.iter().enumerate() {
+                if penetration(&body, corners) > slack(corners, car) {
+                    cells |= 1 << cell;
+                }
+            }
+        }
+        cells
+    }
+
+    // Corners of a cell's rectangle, clockwise from the top left.
+    fn cell_corners(scenario: &Scenario, cell: usize) -> [(f32, f32); 4] {
+        let (xs, ys) = edges(scenario);
+        let (column, row) = (cell % (xs.len() - 1), cell / (xs.len() - 1));
+        let (left, right) = (xs[column], xs[column + 1]);
+        let (top, bottom) = (ys[row], ys[row + 1]);
+        [(left, top), (right, top), (right, bottom), (left, bottom)]
+    }
+
+    /// Whether `car` could take all of `cells`: each is free or already its own.
+    pub fn is_free(&self, cells: Cells, car: u32) -> bool {
+        (0..self.cells.len()).all(|cell| {
+            cells & 1 << cell == 0 || self.cells[cell].is_none_or(|claim| claim.car == car)
+        })
+    }
+
+    pub fn reserve(&mut self, cells: Cells, car: u32) {
+        for (cell, claim) in self.cells.iter_mut().enumerate() {
+            if cells & 1 << cell != 0 && claim.is_none() {
+                *claim = Some(Claim {
+                    car,
+                    entered: false,
+                });
+            }
+        }
+    }
+
+    /// Whether no vehicle holds any cell.
+    pub fn is_empty(&self) -> bool {
+        self.cells.iter().all(Option::is_none)
+    }
+
+    pub fn owner(&self, cell: usize) -> Option<u32> {
+        self.cells[cell].map(|claim| claim.car)
+    }
+
+    /// The vehicles holding any of `cells`.
+    pub fn owners(&self, cells: Cells) -> impl Iterator<Item = u32> + '_ {
+        (0..self.cells.len())
+            .filter(move |cell| cells & 1 << cell != 0)
+            .filter_map(|cell| self.owner(cell))
+    }
+
+    /// Releases the cells whose cars have driven out of them or left the road.
+    pub fn update(&mut self, cars: &[Car], scenario: &Scenario) {
+        for cell in 0..self.cells.len() {
+            let Some(claim) = &mut self.cells[cell] else {
+                continue;
+            };
+            let corners = Self::cell_corners(scenario, cell);
+            let depth = cars
+                .iter()
+                .find(|car| car.id == claim.car)
+                .map(|car| penetration(&car.corners(), &corners) - slack(&corners, car));
+            match depth {
+                Some(depth) if depth > 0.0 => claim.entered = true,
+                Some(_) if !claim.entered => {}
+                _ => self.cells[cell] = None,
+            }
+        }
+    }
+}
+
+// Columns and rows of cells: one per lane of each road.
+fn grid(scenario: &Scenario) -> (usize, usize) {
+    let (xs, ys) = edges(scenario);
+    (xs.len() - 1, ys.len() - 1)
+}
+
+// Edges of the cells across the box from west to east and down it from north
+// to south: a bike lane's width at either end, if there are bike lanes, and a
+// lane's width for each lane of the road in between.
+fn edges(scenario: &Scenario) -> (Vec<f32>, Vec<f32>) {
+    let (left, top, _, _) = scenario.intersection_box();
+    let bike = scenario.bike_lane_width();
+    let cut = |start: f32, lanes: usize| {
+        let inside = start + bike;
+        let mut edges = vec![start];
+        if bike > 0.0 {
+            edges.push(inside);
+        }
+        edges.extend((1..=lanes).map(|lane| inside + lane as f32 * scenario.lane_width));
+        if bike > 0.0 {
+            edges.push(inside + lanes as f32 * scenario.lane_width + bike);
+        }
+        edges
+    };
+    let lanes = |direction| scenario.lanes(direction).len();
+    (
+        cut(left, lanes(Direction::North) + lanes(Direction::South)),
+        cut(top, lanes(Direction::East) + lanes(Direction::West)),
+    )
+}
+
+// How far into a cell a body has to be to count as in it: a quarter of the
+// cell, so a car waiting to turn with its tail just over a cell edge doesn't
+// hold the cell, but less for vehicles too narrow to ever be that deep.
+fn slack(cell: &[(f32, f32); 4], car: &Car) -> f32 {
+    let [(left, top), _, (right, bottom), _] = *cell;
+    ((right - left).min(bottom - top) / 4.0).min(car.width / 4.0)
+}
+
+// Points along the straight line from `from` to `to`.
+fn samples(from: (f32, f32), to: (f32, f32)) -> Vec<(f32, f32)> {
+    let steps = 16;
+    (0..=steps)
+        .map(|i| {
+            let t = i as f32 / steps as f32;
+            (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t)
+        })
+        .collect()
+}
+
+/// How deep two convex quadrilaterals overlap: the smallest overlap of their
+/// projections onto any edge normal, negative when they are apart.
+pub fn penetration(a: &[(f32, f32); 4], b: &[(f32, f32); 4]) -> f32 {
+    let mut depth = f32::INFINITY;
+    for shape in [a, b] {
+        for i in 0..4 {
+            let (x1, y1) = shape[i];
+            let (x2, y2) = shape[(i + 1) % 4];
+            let (nx, ny) = (y1 - y2, x2 - x1);
+            let norm = nx.hypot(ny);
+            if norm == 0.0 {
+                continue;
+            }
+            let project = |points: &[(f32, f32); 4]| {
+                points
+                    .iter()
+                    .fold((f32::MAX, f32::MIN), |(lo, hi), &(x, y)| {
+                        let d = (x * nx + y * ny) / norm;
+                        (lo.min(d), hi.max(d))
+                    })
+            };
+            let (a_lo, a_hi) = project(a);
+            let (b_lo, b_hi) = project(b);
+            depth = depth.min(a_hi.min(b_hi) - a_lo.max(b_lo));
+        }
+    }
+    depth
+}
+
+/// Whether two bodies, given by their corners, overlap by more than the
+/// tolerance.
+pub fn overlaps(a: &[(f32, f32); 4], b: &[(f32, f32); 4]) -> bool {
+    penetration(a, b) > TOLERANCE
+}
+
+#[cfg(feature = "gui")]
+impl ConflictZone {
+    pub fn draw(&self, scenario: &Scenario) {
+        for cell in 0..self.cells.len() {
+            if self.owner(cell).is_some() {
+                let [(left, top), _, (right, bottom), _] = Self::cell_corners(scenario, cell);
+                let tint = Color::new(1.0, 1.0, 1.0, 0.12);
+                draw_rectangle(left, top, right - left, bottom - top, tint);
+            }
+        }
+    }
+}
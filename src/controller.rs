@@ -0,0 +1,960 @@
+use crate::app::{Direction, LightState, Turn};
+use crate::scenario::{Scenario, Scramble, direction_key};
+use rand::{Rng, RngCore};
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// A set of movements (approach × turn) that may proceed at the same time.
+/// Protected movements get a green arrow; permissive ones a flashing amber
+/// arrow and must give way to conflicting traffic. A phase may also give
+/// pedestrians a walk across some legs, a bit per leg, and through the box
+/// diagonally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Phase {
+    protected: u16,
+    permissive: u16,
+    walks: u8,
+}
+
+impl Phase {
+    pub const ALL_RED: Phase = Phase {
+        protected: 0,
+        permissive: 0,
+        walks: 0,
+    };
+
+    /// The scramble: every vehicle signal red and pedestrians walking in
+    /// all directions at once, diagonals included.
+    pub const SCRAMBLE: Phase = Phase {
+        protected: 0,
+        permissive: 0,
+        walks: 0b1_1111,
+    };
+
+    const DIAGONALS: u8 = 1 << 4;
+
+    fn bit(direction: Direction, turn: Turn) -> u16 {
+        1 << (direction.index() * 3 + turn.index())
+    }
+
+    /// Every movement from one approach, protected.
+    pub fn approach(direction: Direction) -> Phase {
+        Turn::ALL.iter().fold(Phase::ALL_RED, |phase, &turn| {
+            phase.protect(direction, turn)
+        })
+    }
+
+    pub fn protect(mut self, direction: Direction, turn: Turn) -> Phase {
+        self.protected |= Self::bit(direction, turn);
+        self.permissive &= !Self::bit(direction, turn);
+        self
+    }
+
+    pub fn permit(mut self, direction: Direction, turn: Turn) -> Phase {
+        self.permissive |= Self::bit(direction, turn);
+        self.protected &= !Self::bit(direction, turn);
+        self
+    }
+
+    /// The (approach, turn) pairs this phase releases.
+    pub fn movements(self) -> impl Iterator<Item = (Direction, Turn)> {
+        Direction::ALL
+            .into_iter()
+            .flat_map(|d| Turn::ALL.map(|t| (d, t)))
+            .filter(move |&(d, t)| self.allows(d, t))
+    }
+
+    pub fn allows(self, direction: Direction, turn: Turn) -> bool {
+        (self.protected | self.permissive) & Self::bit(direction, turn) != 0
+    }
+
+    pub fn is_protected(self, direction: Direction, turn: Turn) -> bool {
+        self.protected & Self::bit(direction, turn) != 0
+    }
+
+    pub fn serves(self, direction: Direction) -> bool {
+        Turn::ALL.iter().any(|&turn| self.allows(direction, turn))
+    }
+
+    /// Gives a walk across the crosswalk on `leg`, the arm of the
+    /// intersection lying in that direction from the box.
+    pub fn walk(mut self, leg: Direction) -> Phase {
+        self.walks |= 1 << leg.index();
+        self
+    }
+
+    pub fn walks(self, leg: Direction) -> bool {
+        self.walks & 1 << leg.index() != 0
+    }
+
+    pub fn walks_diagonally(self) -> bool {
+        self.walks & Self::DIAGONALS != 0
+    }
+
+    /// This phase with a walk on every leg it can share with pedestrians:
+    /// none of its traffic arrives from that leg or goes straight on into
+    /// it, so only turning cars cross and they give way.
+    pub fn with_concurrent_walks(self) -> Phase {
+        Direction::ALL
+            .into_iter()
+            .filter(|&leg| !self.serves(leg.opposite()) && !self.allows(leg, Turn::Front))
+            .fold(self, Phase::walk)
+    }
+
+    /// What the arrow for one movement shows while this phase runs.
+    pub fn signal(self, direction: Direction, turn: Turn) -> LightState {
+        if self.is_protected(direction, turn) {
+            LightState::Green
+        } else if self.allows(direction, turn) {
+            LightState::Flashing
+        } else {
+            LightState::Red
+        }
+    }
+
+    // The movements of `direction` this phase releases.
+    fn released(self, direction: Direction) -> usize {
+        Turn::ALL
+            .iter()
+            .filter(|&&turn| self.allows(direction, turn))
+            .count()
+    }
+}
+
+/// A movement as written in scenario files, e.g. `"north:left"`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Movement {
+    pub approach: Direction,
+    pub turn: Turn,
+}
+
+impl TryFrom<String> for Movement {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        let error =
+            || format!("invalid movement `{s}`, expected `approach:turn`, e.g. `north:left`");
+        let (approach, turn) = s.split_once(':').ok_or_else(error)?;
+        let approach = Direction::ALL
+            .into_iter()
+            .find(|&d| direction_key(d) == approach)
+            .ok_or_else(error)?;
+        let turn = match turn {
+            "left" => Turn::Left,
+            "front" | "straight" => Turn::Front,
+            "right" => Turn::Right,
+            _ => return Err(error()),
+        };
+        Ok(Movement { approach, turn })
+    }
+}
+
+impl fmt::Display for Movement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let turn = match self.turn {
+            Turn::Left => "left",
+            Turn::Front => "front",
+            Turn::Right => "right",
+        };
+        write!(f, "{}:{turn}", direction_key(self.approach))
+    }
+}
+
+/// A phase spelled out in a scenario file.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PhaseSpec {
+    pub protected: Vec<Movement>,
+    pub permissive: Vec<Movement>,
+}
+
+impl PhaseSpec {
+    pub fn phase(&self) -> Phase {
+        let phase = self
+            .protected
+            .iter()
+            .fold(Phase::ALL_RED, |phase, m| phase.protect(m.approach, m.turn));
+        self.permissive
+            .iter()
+            .fold(phase, |phase, m| phase.permit(m.approach, m.turn))
+    }
+}
+
+/// The built-in phase plans the controllers choose from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PhasePlan {
+    // One approach at a time, every movement protected.
+    #[default]
+    Split,
+    // Opposing protected lefts, then the opposing throughs and rights with
+    // the lefts permitted to filter through gaps.
+    LeadingLefts,
+}
+
+impl PhasePlan {
+    pub fn phases(self) -> Vec<Phase> {
+        match self {
+            PhasePlan::Split => Direction::ALL.map(Phase::approach).to_vec(),
+            PhasePlan::LeadingLefts => [Direction::North, Direction::East]
+                .into_iter()
+                .flat_map(|d| {
+                    let o = d.opposite();
+                    let lefts = Phase::ALL_RED.protect(d, Turn::Left).protect(o, Turn::Left);
+                    let throughs = [d, o].into_iter().fold(Phase::ALL_RED, |phase, a| {
+                        phase
+                            .protect(a, Turn::Front)
+                            .protect(a, Turn::Right)
+                            .permit(a, Turn::Left)
+                    });
+                    [lefts, throughs]
+                })
+                .collect(),
+        }
+    }
+}
+
+// The phase of `phases` that does most for `direction`.
+fn phase_for(phases: &[Phase], direction: Direction) -> Phase {
+    phases
+        .iter()
+        .copied()
+        .filter(|p| p.serves(direction))
+        .fold(None, |best: Option<Phase>, p| match best {
+            Some(b) if b.released(direction) >= p.released(direction) => Some(b),
+            _ => Some(p),
+        })
+        .unwrap_or_else(|| Phase::approach(direction))
+}
+
+/// What a controller sees at a decision point. Per-approach arrays are
+/// indexed by `Direction::index`.
+pub struct ControllerView {
+    pub time: f64,
+    // Cars counted in on each approach that have not yet passed their turn point.
+    pub counts: [u32; 4],
+    // Cars standing still before the stop line.
+    pub stopped: [u32; 4],
+    // Cars still on each approach, by intended turn (`Turn::index`).
+    pub queues: [[u32; 3]; 4],
+    // Cars that have left their approach and now travel in each direction,
+    // i.e. occupancy of the exit legs.
+    pub downstream: [u32; 4],
+    // Whether a presence loop on the approach is occupied.
+    pub presence: [bool; 4],
+    // Latest arrival over a passage loop on the approach.
+    pub last_passage: [Option<f64>; 4],
+    // Seconds the longest-waiting pedestrian at each leg's crosswalk has
+    // been at the kerb, by leg; 0 with nobody waiting.
+    pub pedestrian_wait: [f64; 4],
+    // The same for those waiting to cross diagonally.
+    pub diagonal_wait: f64,
+    pub current: Phase,
+    // Seconds the current phase has been green.
+    pub elapsed: f64,
+}
+
+/// Decides which phase the signals run next. Returning the current phase
+/// extends it; anything else ends it through the all-red clearance.
+pub trait SignalController {
+    fn name(&self) -> &'static str;
+
+    fn next_phase(&mut self, view: &ControllerView, rng: &mut dyn RngCore) -> Phase;
+}
+
+/// Greens the approach with the most cars counted in, falling back to a
+/// random approach when there is no clear winner, using whichever phase of
+/// the plan does most for it.
+#[derive(Debug)]
+pub struct MaxQueue {
+    phases: Vec<Phase>,
+}
+
+impl Default for MaxQueue {
+    fn default() -> Self {
+        Self::new(PhasePlan::Split.phases())
+    }
+}
+
+impl MaxQueue {
+    pub fn new(phases: Vec<Phase>) -> Self {
+        Self { phases }
+    }
+}
+
+impl SignalController for MaxQueue {
+    fn name(&self) -> &'static str {
+        "max-queue"
+    }
+
+    fn next_phase(&mut self, view: &ControllerView, rng: &mut dyn RngCore) -> Phase {
+        let [north, south, east, west] = view.counts;
+        let direction = if north >= south && north > east && north > west {
+            Direction::North
+        } else if south >= north && south > east && south > west {
+            Direction::South
+        } else if east >= north && east > south && east > west {
+            Direction::East
+        } else if west >= north && west > south && west > east {
+            Direction::West
+        } else {
+            Direction::ALL[rng.random_range(0..Direction::ALL.len())]
+        };
+
+        // Among the phases serving it, the one releasing most of its queue.
+        let queued = |phase: Phase| -> u32 {
+            Turn::ALL
+                .iter()
+                .filter(|&&turn| phase.allows(direction, turn))
+                .map(|turn| view.queues[direction.index()][turn.index()])
+                .sum()
+        };
+        if view.current.serves(direction)
+            && self
+                .phases
+                .iter()
+                .all(|&p| queued(p) <= queued(view.current))
+        {
+            return view.current;
+        }
+        let mut best = phase_for(&self.phases, direction);
+        for &phase in &self.phases {
+            if phase.serves(direction) && queued(phase) > queued(best) {
+                best = phase;
+            }
+        }
+        best
+    }
+}
+
+/// Fixed-time control: each phase of the plan in turn, regardless of demand.
+#[derive(Debug)]
+pub struct RoundRobin {
+    phases: Vec<Phase>,
+}
+
+impl Default for RoundRobin {
+    fn default() -> Self {
+        Self::new(PhasePlan::Split.phases())
+    }
+}
+
+impl RoundRobin {
+    pub fn new(phases: Vec<Phase>) -> Self {
+        Self { phases }
+    }
+}
+
+impl SignalController for RoundRobin {
+    fn name(&self) -> &'static str {
+        "round-robin"
+    }
+
+    fn next_phase(&mut self, view: &ControllerView, _rng: &mut dyn RngCore) -> Phase {
+        // Step on from whatever is running, so a proposal held back by the
+        // green-time limits is not skipped.
+        let current = self.phases.iter().position(|&p| p == view.current);
+        self.phases[current.map_or(0, |i| (i + 1) % self.phases.len())]
+    }
+}
+
+/// Picks a phase uniformly at random; a baseline for the others.
+#[derive(Debug)]
+pub struct RandomController {
+    phases: Vec<Phase>,
+}
+
+impl Default for RandomController {
+    fn default() -> Self {
+        Self::new(PhasePlan::Split.phases())
+    }
+}
+
+impl RandomController {
+    pub fn new(phases: Vec<Phase>) -> Self {
+        Self { phases }
+    }
+}
+
+impl SignalController for RandomController {
+    fn name(&self) -> &'static str {
+        "random"
+    }
+
+    fn next_phase(&mut self, _view: &ControllerView, rng: &mut dyn RngCore) -> Phase {
+        self.phases[rng.random_range(0..self.phases.len())]
+    }
+}
+
+/// Vehicle-actuated control from the detector loops: keeps a green while
+/// cars arrive over the passage loops within `gap` seconds of each other
+/// (gap-out), then moves on to the next phase with a call. Max-out comes
+/// from the green-time limits. Pedestrians waiting at a crosswalk call the
+/// phases that walk it, as if they had pushed the button.
+#[derive(Debug)]
+pub struct Actuated {
+    phases: Vec<Phase>,
+    gap: f64,
+    // Phases with a car detected on one of their approaches, or someone
+    // waiting to cross with them, since they were last green.
+    calls: Vec<bool>,
+}
+
+impl Actuated {
+    pub fn new(phases: Vec<Phase>, gap: f64) -> Self {
+        Self {
+            calls: vec![false; phases.len()],
+            phases,
+            gap,
+        }
+    }
+}
+
+impl SignalController for Actuated {
+    fn name(&self) -> &'static str {
+        "actuated"
+    }
+
+    fn next_phase(&mut self, view: &ControllerView, _rng: &mut dyn RngCore) -> Phase {
+        let detected = Direction::ALL.map(|d| {
+            let i = d.index();
+            view.presence[i] || view.last_passage[i].is_some_and(|t| t >= view.time - view.elapsed)
+        });
+        // The loops cover a whole approach, so a detection only counts for
+        // a phase if cars are queued for a movement it releases.
+        let demand = |phase: Phase, d: Direction| {
+            Turn::ALL
+                .iter()
+                .any(|&t| phase.allows(d, t) && view.queues[d.index()][t.index()] > 0)
+        };
+        for (&phase, call) in self.phases.iter().zip(&mut self.calls) {
+            if phase == view.current {
+                *call = false;
+            } else if Direction::ALL.into_iter().any(|d| {
+                detected[d.index()] && demand(phase, d)
+                    || phase.walks(d) && view.pedestrian_wait[d.index()] > 0.0
+            }) {
+                *call = true;
+            }
+        }
+
+        let serving = self.phases.iter().position(|&p| p == view.current);
+        let start = serving.map_or(0, |i| i + 1);
+        let count = self.phases.len();
+        let next_call = (0..count)
+            .map(|k| (start + k) % count)
+            .find(|&i| self.calls[i] && Some(i) != serving)
+            .map(|i| self.phases[i]);
+
+        if view.current == Phase::ALL_RED {
+            return next_call.unwrap_or(Phase::ALL_RED);
+        }
+
+        // A car standing on the presence loop only holds the green if the
+        // phase releases everything queued there, else it may be waiting for
+        // a movement this phase doesn't serve.
+        let clears = |d: Direction| {
+            Turn::ALL
+                .iter()
+                .all(|&t| view.current.allows(d, t) || view.queues[d.index()][t.index()] == 0)
+        };
+        let mut extend = false;
+        for direction in Direction::ALL {
+            let i = direction.index();
+            let gap = view.last_passage[i].map_or(f64::INFINITY, |t| view.time - t);
+            extend |= gap <= self.gap && demand(view.current, direction)
+                || view.presence[i] && view.current.serves(direction) && clears(direction);
+        }
+        if extend {
+            return view.current;
+        }
+        // Rest in green while nobody else is waiting.
+        next_call.unwrap_or(view.current)
+    }
+}
+
+/// Max-pressure control: scores each phase by the cars queued for its
+/// movements minus the cars already on those movements' exit legs, and runs
+/// the highest-scoring phase for the next decision interval.
+#[derive(Debug)]
+pub struct MaxPressure {
+    phases: Vec<Phase>,
+}
+
+impl Default for MaxPressure {
+    fn default() -> Self {
+        Self::new(PhasePlan::Split.phases())
+    }
+}
+
+impl MaxPressure {
+    pub fn new(phases: Vec<Phase>) -> Self {
+        Self { phases }
+    }
+
+    pub fn pressure(phase: Phase, view: &ControllerView) -> i64 {
+        phase
+            .movements()
+            .map(|(direction, turn)| {
+                let upstream = view.queues[direction.index()][turn.index()] as i64;
+                let downstream = view.downstream[direction.turned(turn).index()] as i64;
+                upstream - downstream
+            })
+            .sum()
+    }
+}
+
+impl SignalController for MaxPressure {
+    fn name(&self) -> &'static str {
+        "max-pressure"
+    }
+
+    fn next_phase(&mut self, view: &ControllerView, _rng: &mut dyn RngCore) -> Phase {
+        // Ties keep the current phase, then go to the earliest listed.
+        let current = Self::pressure(view.current, view);
+        let mut best = (view.current, current);
+        for &phase in &self.phases {
+            let pressure = Self::pressure(phase, view);
+            if pressure > best.1 || best.0 == Phase::ALL_RED && pressure == best.1 {
+                best = (phase, pressure);
+            }
+        }
+        best.0
+    }
+}
+
+/// Per-approach timing guarantees enforced around any controller.
+#[derive(Debug, Clone, Copy)]
+pub struct GreenLimits {
+    // Seconds a green must run before it may end.
+    pub min_green: f64,
+    // Seconds after which a green ends if another approach is waiting.
+    pub max_green: f64,
+    // Seconds an approach with cars may go unserved before it is forced green.
+    pub max_wait: f64,
+}
+
+/// How often each guarantee overrode the controller, per approach.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GuardStats {
+    pub min_green_holds: [u32; 4],
+    pub max_green_cutoffs: [u32; 4],
+    pub max_wait_overrides: [u32; 4],
+    // Walks forced for pedestrians who had waited too long, by leg.
+    pub pedestrian_overrides: [u32; 4],
+    // Scrambles run between the controller's phases.
+    pub scrambles: u32,
+}
+
+/// Wraps a controller with minimum and maximum green times and a maximum
+/// wait, so no strategy can flicker the lights or starve an approach or a
+/// crosswalk, and slots in the scramble where it is due.
+pub struct GreenGuard {
+    inner: Box<dyn SignalController>,
+    limits: [GreenLimits; 4],
+    // Seconds a pedestrian may wait before their crosswalk is forced a walk.
+    pedestrian_max_wait: f64,
+    scramble: Scramble,
+    // Greens started since the last scramble, and the phase last seen.
+    greens: u32,
+    last_phase: Phase,
+    // Plan the overrides pick their phases from.
+    phases: Vec<Phase>,
+    pub stats: GuardStats,
+    // When each approach was last green, and since when it has had cars.
+    last_served: [f64; 4],
+    demand_since: [Option<f64>; 4],
+}
+
+impl GreenGuard {
+    pub fn new(
+        inner: Box<dyn SignalController>,
+        limits: [GreenLimits; 4],
+        pedestrian_max_wait: f64,
+        scramble: Scramble,
+        phases: Vec<Phase>,
+    ) -> Self {
+        Self {
+            inner,
+            limits,
+            pedestrian_max_wait,
+            scramble,
+            greens: 0,
+            last_phase: Phase::ALL_RED,
+            phases,
+            stats: GuardStats::default(),
+            last_served: [0.0; 4],
+            demand_since: [None; 4],
+        }
+    }
+
+    pub fn set_inner(&mut self, inner: Box<dyn SignalController>) {
+        self.inner = inner;
+    }
+
+    fn served(phase: Phase) -> impl Iterator<Item = Direction> {
+        Direction::ALL.into_iter().filter(move |&d| phase.serves(d))
+    }
+
+    // Seconds each approach has had cars without being served.
+    fn waits(&mut self, view: &ControllerView) -> [f64; 4] {
+        let mut waits = [0.0; 4];
+        for direction in Direction::ALL {
+            let i = direction.index();
+            if view.current.serves(direction) {
+                self.last_served[i] = view.time;
+            }
+            if view.counts[i] == 0 {
+                self.demand_since[i] = None;
+                continue;
+            }
+            let since = *self.demand_since[i].get_or_insert(view.time);
+            waits[i] = view.time - since.max(self.last_served[i]);
+        }
+        waits
+    }
+
+    // Whether to run the scramble instead of the next phase: when the phase
+    // is `changing` anyway and a scramble is due on the schedule or called
+    // by someone waiting to cross diagonally, or when they have waited too
+    // long.
+    fn scramble_due(&self, view: &ControllerView, changing: bool) -> bool {
+        if view.current.walks_diagonally() {
+            return false;
+        }
+        let cycle = self.phases.len() as u32;
+        let scheduled = self.scramble.every > 0 && self.greens >= self.scramble.every * cycle;
+        let called = self.scramble.on_demand && view.diagonal_wait > 0.0;
+        (scheduled || called) && changing || view.diagonal_wait >= self.pedestrian_max_wait
+    }
+
+    fn scramble(&mut self) -> Phase {
+        self.stats.scrambles += 1;
+        self.greens = 0;
+        Phase::SCRAMBLE
+    }
+
+    // The unserved approach with cars that has waited longest.
+    fn longest_waiting(&self, waits: &[f64; 4], current: Phase) -> Option<Direction> {
+        Direction::ALL
+            .into_iter()
+            .filter(|&d| !current.serves(d) && waits[d.index()] > 0.0)
+            .max_by(|a, b| waits[a.index()].total_cmp(&waits[b.index()]))
+    }
+}
+
+impl SignalController for GreenGuard {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn next_phase(&mut self, view: &ControllerView, rng: &mut dyn RngCore) -> Phase {
+        let waits = self.waits(view);
+        let current = view.current;
+        if current != self.last_phase {
+            self.last_phase = current;
+            if current.movements().next().is_some() {
+                self.greens += 1;
+            }
+        }
+        let proposal = self.inner.next_phase(view, rng);
+
+        if current != Phase::ALL_RED {
+            let min_green = Self::served(current)
+                .map(|d| self.limits[d.index()].min_green)
+                .fold(0.0, f64::max);
+            if view.elapsed < min_green {
+                if proposal != current {
+                    for d in Self::served(current) {
+                        self.stats.min_green_holds[d.index()] += 1;
+                    }
+                }
+                return current;
+            }
+        }
+
+        // Ahead of any starved approach, which is served straight after:
+        // only a scramble lets anyone cross diagonally.
+        if self.scramble_due(view, proposal != current) {
+            return self.scramble();
+        }
+
+        let starved = Direction::ALL
+            .into_iter()
+            .filter(|&d| !proposal.serves(d) && waits[d.index()] >= self.limits[d.index()].max_wait)
+            .max_by(|a, b| waits[a.index()].total_cmp(&waits[b.index()]));
+        if let Some(direction) = starved {
+            self.stats.max_wait_overrides[direction.index()] += 1;
+            return phase_for(&self.phases, direction);
+        }
+
+        // Someone who has waited too long keeps a phase that walks their leg
+        // until they have stepped off, even if one walked it before.
+        let stranded = Direction::ALL
+            .into_iter()
+            .filter(|&leg| view.pedestrian_wait[leg.index()] >= self.pedestrian_max_wait)
+            .max_by(|a, b| {
+                view.pedestrian_wait[a.index()].total_cmp(&view.pedestrian_wait[b.index()])
+            });
+        if let Some(leg) = stranded
+            && let Some(phase) = [current, proposal]
+                .into_iter()
+                .chain(self.phases.iter().copied())
+                .find(|p| p.walks(leg))
+        {
+            if phase != proposal {
+                self.stats.pedestrian_overrides[leg.index()] += 1;
+            }
+            return phase;
+        }
+
+        if proposal == current && current != Phase::ALL_RED {
+            let max_green = Self::served(current)
+                .map(|d| self.limits[d.index()].max_green)
+                .fold(f64::INFINITY, f64::min);
+            if view.elapsed >= max_green
+                && let Some(direction) = self.longest_waiting(&waits, current)
+            {
+                for d in Self::served(current) {
+                    self.stats.max_green_cutoffs[d.index()] += 1;
+                }
+                if self.scramble_due(view, true) {
+                    return self.scramble();
+                }
+                return phase_for(&self.phases, direction);
+            }
+        }
+
+        proposal
+    }
+}
+
+/// The built-in controllers, as named in scenario files and on the command line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ControllerKind {
+    #[default]
+    MaxQueue,
+    RoundRobin,
+    Random,
+    Actuated,
+    MaxPressure,
+}
+
+impl ControllerKind {
+    pub const ALL: [ControllerKind; 5] = [
+        ControllerKind::MaxQueue,
+        ControllerKind::RoundRobin,
+        ControllerKind::Random,
+        ControllerKind::Actuated,
+        ControllerKind::MaxPressure,
+    ];
+
+    pub fn build(self, scenario: &Scenario) -> Box<dyn SignalController> {
+        let phases = scenario.phases();
+        match self {
+            ControllerKind::MaxQueue => Box::new(MaxQueue::new(phases)),
+            ControllerKind::RoundRobin => Box::new(RoundRobin::new(phases)),
+            ControllerKind::Random => Box::new(RandomController::new(phases)),
+            ControllerKind::Actuated => {
+                Box::new(Actuated::new(phases, scenario.signals.gap as f64))
+            }
+            ControllerKind::MaxPressure => Box::new(MaxPressure::new(phases)),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ControllerKind::MaxQueue => "max-queue",
+            ControllerKind::RoundRobin => "round-robin",
+            ControllerKind::Random => "random",
+            ControllerKind::Actuated => "actuated",
+            ControllerKind::MaxPressure => "max-pressure",
+        }
+    }
+}
+
+impl fmt::Display for ControllerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ControllerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ControllerKind::ALL
+            .into_iter()
+            .find(|kind| kind.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = ControllerKind::ALL.iter().map(|k| k.name()).collect();
+                format!(
+                    "unknown controller `{s}`, expected one of {}",
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    // A decision point `elapsed` seconds into `current` with nothing on the
+    // road and nobody waiting.
+    fn view(current: Phase, time: f64, elapsed: f64) -> ControllerView {
+        ControllerView {
+            time,
+            counts: [0; 4],
+            stopped: [0; 4],
+            queues: [[0; 3]; 4],
+            downstream: [0; 4],
+            presence: [false; 4],
+            last_passage: [None; 4],
+            pedestrian_wait: [0.0; 4],
+            diagonal_wait: 0.0,
+            current,
+            elapsed,
+        }
+    }
+
+    // Proposes the same phase whatever it sees.
+    struct Fixed(Phase);
+
+    impl SignalController for Fixed {
+        fn name(&self) -> &'static str {
+            "fixed"
+        }
+
+        fn next_phase(&mut self, _view: &ControllerView, _rng: &mut dyn RngCore) -> Phase {
+            self.0
+        }
+    }
+
+    fn guard(proposal: Phase, phases: Vec<Phase>) -> GreenGuard {
+        let limits = GreenLimits {
+            min_green: 2.0,
+            max_green: 8.0,
+            max_wait: 12.0,
+        };
+        GreenGuard::new(
+            Box::new(Fixed(proposal)),
+            [limits; 4],
+            30.0,
+            Scramble::default(),
+            phases,
+        )
+    }
+
+    #[test]
+    fn guard_holds_a_green_until_min_green() {
+        let north = Phase::approach(Direction::North);
+        let east = Phase::approach(Direction::East);
+        let mut guard = guard(east, PhasePlan::Split.phases());
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        assert_eq!(guard.next_phase(&view(north, 1.0, 1.0), &mut rng), north);
+        assert_eq!(guard.stats.min_green_holds[Direction::North.index()], 1);
+        assert_eq!(guard.next_phase(&view(north, 2.0, 2.0), &mut rng), east);
+    }
+
+    #[test]
+    fn guard_ends_a_green_at_max_green_for_an_approach_waiting() {
+        let north = Phase::approach(Direction::North);
+        let mut guard = guard(north, PhasePlan::Split.phases());
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let mut waiting = view(north, 0.0, 0.0);
+        waiting.counts[Direction::East.index()] = 1;
+        guard.next_phase(&waiting, &mut rng);
+        waiting.time = 7.0;
+        waiting.elapsed = 7.0;
+        assert_eq!(guard.next_phase(&waiting, &mut rng), north);
+        waiting.time = 8.0;
+        waiting.elapsed = 8.0;
+        let next = guard.next_phase(&waiting, &mut rng);
+        assert_eq!(next, Phase::approach(Direction::East));
+        assert_eq!(guard.stats.max_green_cutoffs[Direction::North.index()], 1);
+    }
+
+    #[test]
+    fn guard_forces_an_approach_waiting_past_max_wait() {
+        let north = Phase::approach(Direction::North);
+        let south = Phase::approach(Direction::South);
+        let mut guard = guard(south, PhasePlan::Split.phases());
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let mut waiting = view(north, 0.0, 0.0);
+        waiting.counts[Direction::West.index()] = 1;
+        guard.next_phase(&waiting, &mut rng);
+        waiting.time = 12.0;
+        waiting.elapsed = 4.0;
+        let next = guard.next_phase(&waiting, &mut rng);
+        assert_eq!(next, Phase::approach(Direction::West));
+        assert_eq!(guard.stats.max_wait_overrides[Direction::West.index()], 1);
+    }
+
+    #[test]
+    fn guard_forces_a_walk_for_someone_waiting_past_max_wait() {
+        let north = Phase::approach(Direction::North);
+        let walking = Phase::approach(Direction::East).walk(Direction::North);
+        let mut guard = guard(north, vec![north, walking]);
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let mut waiting = view(north, 30.0, 4.0);
+        waiting.pedestrian_wait[Direction::North.index()] = 29.0;
+        assert_eq!(guard.next_phase(&waiting, &mut rng), north);
+        waiting.pedestrian_wait[Direction::North.index()] = 30.0;
+        assert_eq!(guard.next_phase(&waiting, &mut rng), walking);
+        assert_eq!(
+            guard.stats.pedestrian_overrides[Direction::North.index()],
+            1
+        );
+    }
+
+    #[test]
+    fn actuated_extends_a_green_while_cars_keep_coming_then_gaps_out() {
+        let north = Phase::approach(Direction::North);
+        let mut actuated = Actuated::new(PhasePlan::Split.phases(), 3.0);
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let mut arriving = view(north, 20.0, 10.0);
+        arriving.queues[Direction::North.index()][Turn::Front.index()] = 1;
+        arriving.last_passage[Direction::North.index()] = Some(18.0);
+        arriving.queues[Direction::East.index()][Turn::Front.index()] = 2;
+        arriving.presence[Direction::East.index()] = true;
+        assert_eq!(actuated.next_phase(&arriving, &mut rng), north);
+
+        arriving.time = 22.0;
+        arriving.elapsed = 12.0;
+        let next = actuated.next_phase(&arriving, &mut rng);
+        assert_eq!(next, Phase::approach(Direction::East));
+    }
+
+    #[test]
+    fn actuated_rests_in_green_without_a_call() {
+        let north = Phase::approach(Direction::North);
+        let mut actuated = Actuated::new(PhasePlan::Split.phases(), 3.0);
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        assert_eq!(
+            actuated.next_phase(&view(north, 20.0, 10.0), &mut rng),
+            north
+        );
+    }
+
+    #[test]
+    fn max_pressure_weighs_queues_against_full_exits() {
+        let north = Phase::approach(Direction::North);
+        let mut max_pressure = MaxPressure::new(PhasePlan::Split.phases());
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let mut queued = view(Phase::approach(Direction::West), 10.0, 4.0);
+        queued.queues[Direction::North.index()][Turn::Left.index()] = 5;
+        queued.queues[Direction::East.index()][Turn::Front.index()] = 3;
+        assert_eq!(max_pressure.next_phase(&queued, &mut rng), north);
+
+        // The northbound left turners' exit is all but full.
+        queued.downstream[Direction::North.left().index()] = 4;
+        let next = max_pressure.next_phase(&queued, &mut rng);
+        assert_eq!(next, Phase::approach(Direction::East));
+    }
+}
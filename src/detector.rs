@@ -0,0 +1,131 @@
+use crate::app::{Car, Direction};
+use crate::scenario::Scenario;
+#[cfg(feature = "gui")]
+use macroquad::prelude::*;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DetectorKind {
+    // Long loop at the stop line: reports whether a car is standing on it.
+    Presence,
+    // Short loop upstream: counts cars arriving over it.
+    Passage,
+}
+
+/// A virtual induction loop on an approach lane, placed `distance` pixels
+/// upstream of the stop line and `length` pixels long.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DetectorSpec {
+    pub approach: Direction,
+    pub kind: DetectorKind,
+    pub distance: f32,
+    pub length: f32,
+}
+
+impl DetectorSpec {
+    /// The default loops: a presence loop at each stop line and a passage
+    /// loop further back on each approach.
+    pub fn standard() -> Vec<DetectorSpec> {
+        Direction::ALL
+            .into_iter()
+            .flat_map(|approach| {
+                [
+                    DetectorSpec {
+                        approach,
+                        kind: DetectorKind::Presence,
+                        distance: 0.0,
+                        length: 60.0,
+                    },
+                    DetectorSpec {
+                        approach,
+                        kind: DetectorKind::Passage,
+                        distance: 150.0,
+                        length: 10.0,
+                    },
+                ]
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Detector {
+    pub spec: DetectorSpec,
+    pub occupied: bool,
+    // Cars that have arrived over the loop so far.
+    pub passages: u32,
+    pub last_passage: Option<f64>,
+}
+
+impl Detector {
+    pub fn new(spec: DetectorSpec) -> Self {
+        Self {
+            spec,
+            occupied: false,
+            passages: 0,
+            last_passage: None,
+        }
+    }
+
+    /// The loop's extent along its approach, as (upstream, downstream) edges.
+    pub fn zone(&self, scenario: &Scenario) -> (f32, f32) {
+        let end = scenario.stop_line(self.spec.approach) - self.spec.distance;
+        (end - self.spec.length, end)
+    }
+
+    pub fn update(&mut self, cars: &[Car], scenario: &Scenario, now: f64) {
+        let direction = self.spec.approach;
+        let (start, end) = self.zone(scenario);
+        let (inside, outside) = Self::span(scenario, direction);
+
+        let occupied = cars.iter().any(|car| {
+            let along = direction.along(car.x, car.y);
+            let lateral = direction.lateral(car.x, car.y);
+            car.heading == direction
+                && lateral > inside
+                && lateral < outside
+                && along + car.length / 2.0 > start
+                && along - car.length / 2.0 < end
+        });
+
+        // A passage is the leading edge of a car arriving on the loop.
+        if occupied && !self.occupied {
+            self.passages += 1;
+            self.last_passage = Some(now);
+        }
+        self.occupied = occupied;
+    }
+
+    // The loop's extent across the road: every lane of its approach.
+    fn span(scenario: &Scenario, direction: Direction) -> (f32, f32) {
+        let (x, y) = scenario.lane_point(direction, 0);
+        let inside = direction.lateral(x, y) - scenario.lane_width / 2.0;
+        let lanes = scenario.lanes(direction).len() as f32;
+        (inside, inside + lanes * scenario.lane_width)
+    }
+}
+
+#[cfg(feature = "gui")]
+impl Detector {
+    pub fn draw(&self, scenario: &Scenario) {
+        let direction = self.spec.approach;
+        let (start, end) = self.zone(scenario);
+        let (inside, outside) = Self::span(scenario, direction);
+        let (vx, vy) = direction.vector();
+        let (rx, ry) = direction.right().vector();
+        let along = (start + end) / 2.0;
+        let lateral = (inside + outside) / 2.0;
+        // `along` and `lateral` are projections onto the unit vectors.
+        let (cx, cy) = (vx * along + rx * lateral, vy * along + ry * lateral);
+
+        let across = outside - inside - 10.0;
+        let (w, h) = match direction {
+            Direction::North | Direction::South => (across, self.spec.length),
+            Direction::East | Direction::West => (self.spec.length, across),
+        };
+        let color = if self.occupied { SKYBLUE } else { GRAY };
+        draw_rectangle_lines(cx - w / 2.0, cy - h / 2.0, w, h, 2.0, color);
+    }
+}
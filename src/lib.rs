@@ -0,0 +1,16 @@
+pub mod app;
+pub mod conflict;
+pub mod controller;
+pub mod detector;
+pub mod pedestrian;
+#[cfg(feature = "gui")]
+pub mod render;
+pub mod scenario;
+pub mod simulation;
+pub mod stats;
+
+pub use app::{Car, Col, Direction, LightState, TrafficLight, Turn, VehicleClass};
+pub use controller::{ControllerKind, Phase, PhasePlan, SignalController};
+pub use scenario::{Control, Scenario};
+pub use simulation::{Simulation, SimulationError};
+pub use stats::Statistics;
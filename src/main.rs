@@ -1,54 +1,58 @@
-mod app;
-use ::rand::{Rng, rng};
-use app::*;
 use macroquad::prelude::*;
+use road_intersection::render::{draw_lane_markings, draw_roads, draw_stop_signs};
+use road_intersection::simulation::TICK;
+use road_intersection::{Control, ControllerKind, Direction, Scenario, Simulation};
+use std::env;
+use std::process;
+use std::sync::OnceLock;
+
+const MAX_FRAME_TIME: f32 = 0.25;
+
+// Loaded once so the window size and the simulation agree.
+fn scenario() -> &'static Scenario {
+    static SCENARIO: OnceLock<Scenario> = OnceLock::new();
+    SCENARIO.get_or_init(|| match arg_value("--scenario") {
+        Some(path) => Scenario::load(&path).unwrap_or_else(|err| {
+            eprintln!("{path}: {err}");
+            process::exit(2);
+        }),
+        None => Scenario::default(),
+    })
+}
 
-const WINDOW_WIDTH: f32 = 800.0;
-const WINDOW_HEIGHT: f32 = 600.0;
-const LANE_WIDTH: f32 = 50.0;
+fn window_conf() -> Conf {
+    let scenario = scenario();
+    Conf {
+        window_title: "Traffic Simulation".to_owned(),
+        window_width: scenario.window.width as i32,
+        window_height: scenario.window.height as i32,
+        ..Default::default()
+    }
+}
 
-#[macroquad::main("Traffic Simulation")]
+#[macroquad::main(window_conf)]
 async fn main() {
-    let mut cars: Vec<Car> = Vec::new();
-    let colors: Vec<(Col, Turn)> = vec![
-        (Col::Darkblue, Turn::Left),
-        (Col::Pink, Turn::Right),
-        (Col::Gold, Turn::Front),
-    ];
-
-    let mut lights = vec![
-        TrafficLight::new(
-            Direction::North,
-            WINDOW_WIDTH / 2.0 - 90.0,
-            WINDOW_HEIGHT / 2.0 - 100.0,
-            false,
-        ),
-        TrafficLight::new(
-            Direction::South,
-            WINDOW_WIDTH / 2.0 + LANE_WIDTH,
-            WINDOW_HEIGHT / 2.0 + LANE_WIDTH,
-            false,
-        ),
-        TrafficLight::new(
-            Direction::East,
-            WINDOW_WIDTH / 2.0 + LANE_WIDTH,
-            WINDOW_HEIGHT / 2.0 - 100.0,
-            false,
-        ),
-        TrafficLight::new(
-            Direction::West,
-            WINDOW_WIDTH / 2.0 - 90.0,
-            WINDOW_HEIGHT / 2.0 + LANE_WIDTH,
-            false,
-        ),
-    ];
-
-    let mut last_change = get_time();
-    let mut all_red_start: Option<f64> = None;
-    let mut north_count = 0;
-    let mut south_count = 0;
-    let mut east_count = 0;
-    let mut west_count = 0;
+    let seed = seed_from_args().unwrap_or_else(::rand::random);
+    println!("seed: {seed}");
+    let mut scenario = scenario().clone();
+    if let Some(name) = arg_value("--controller") {
+        match name.parse::<ControllerKind>() {
+            Ok(kind) => scenario.signals.controller = kind,
+            Err(err) => eprintln!("{err}"),
+        }
+    }
+    if let Some(name) = arg_value("--control") {
+        match name.parse::<Control>() {
+            Ok(control) => scenario.control = control,
+            Err(err) => eprintln!("{err}"),
+        }
+        if let Err(err) = scenario.validate() {
+            eprintln!("{err}");
+            process::exit(2);
+        }
+    }
+    let mut sim = Simulation::new(scenario, seed);
+    let mut accumulator = 0.0;
 
     loop {
         if is_key_pressed(KeyCode::Escape) {
@@ -56,540 +60,93 @@ async fn main() {
         }
 
         if is_key_pressed(KeyCode::Up) {
-            let mut rng = rng();
-            let (col, turn) = colors[rng.random_range(0..colors.len())];
-            let color = Color::from(col);
-            let start_x = WINDOW_WIDTH / 2.0;
-            if can_spawn(&cars, start_x, 50.0, 0.0, -SPEED) {
-                north_count += 1;
-                cars.push(Car::new(
-                    Direction::North,
-                    start_x,
-                    WINDOW_HEIGHT + LANE_WIDTH,
-                    0.0,
-                    -SPEED,
-                    true,
-                    color,
-                    turn,
-                ));
-            }
+            sim.spawn(Direction::North);
         }
-
         if is_key_pressed(KeyCode::Down) {
-            let mut rng = rng();
-            let (col, turn) = colors[rng.random_range(0..colors.len())];
-            let color = Color::from(col);
-            let start_x = WINDOW_WIDTH / 2.0 - LANE_WIDTH;
-            if can_spawn(&cars, start_x, 50.0, 0.0, SPEED) {
-                south_count += 1;
-                cars.push(Car::new(
-                    Direction::South,
-                    start_x,
-                    -LANE_WIDTH,
-                    0.0,
-                    SPEED,
-                    true,
-                    color,
-                    turn,
-                ));
-            }
+            sim.spawn(Direction::South);
         }
-
         if is_key_pressed(KeyCode::Right) {
-            let mut rng = rng();
-            let (col, turn) = colors[rng.random_range(0..colors.len())];
-            let color = Color::from(col);
-            let start_y = WINDOW_HEIGHT / 2.0;
-            if can_spawn(&cars, start_y, 50.0, SPEED, 0.0) {
-                east_count += 1;
-                cars.push(Car::new(
-                    Direction::East,
-                    -LANE_WIDTH,
-                    start_y,
-                    SPEED,
-                    0.0,
-                    true,
-                    color,
-                    turn,
-                ));
-            }
+            sim.spawn(Direction::East);
         }
-
         if is_key_pressed(KeyCode::Left) {
-            let mut rng = rng();
-            let (col, turn) = colors[rng.random_range(0..colors.len())];
-            let color = Color::from(col);
-            let start_y = WINDOW_HEIGHT / 2.0 - LANE_WIDTH;
-            if can_spawn(&cars, start_y, 50.0, -SPEED, 0.0) {
-                west_count += 1;
-                cars.push(Car::new(
-                    Direction::West,
-                    WINDOW_WIDTH + LANE_WIDTH,
-                    start_y,
-                    -SPEED,
-                    0.0,
-                    true,
-                    color,
-                    turn,
-                ));
-            }
+            sim.spawn(Direction::West);
         }
-
         if is_key_pressed(KeyCode::R) {
-            let mut rng = rng();
-            let direction = match rng.random_range(0..4) {
-                0 => Direction::North,
-                1 => Direction::South,
-                2 => Direction::East,
-                _ => Direction::West,
-            };
-
-            let (start_x, start_y, velocity_x, velocity_y) = match direction {
-                Direction::North => (WINDOW_WIDTH / 2.0, WINDOW_HEIGHT + LANE_WIDTH, 0.0, -SPEED),
-                Direction::South => (WINDOW_WIDTH / 2.0 - LANE_WIDTH, -LANE_WIDTH, 0.0, SPEED),
-                Direction::East => (-LANE_WIDTH, WINDOW_HEIGHT / 2.0, SPEED, 0.0),
-                Direction::West => (
-                    WINDOW_WIDTH + LANE_WIDTH,
-                    WINDOW_HEIGHT / 2.0 - LANE_WIDTH,
-                    -SPEED,
-                    0.0,
-                ),
-            };
-
-            let (col, turn) = colors[rng.random_range(0..colors.len())];
-            let color = Color::from(col);
-
-            if can_spawn(&cars, start_y, 50.0, velocity_x, velocity_y) {
-                cars.push(Car::new(
-                    direction, start_x, start_y, velocity_x, velocity_y, true, color, turn,
-                ));
-            }
+            sim.spawn_random();
+        }
+        if is_key_pressed(KeyCode::E) {
+            sim.spawn_emergency_random();
+        }
+        if is_key_pressed(KeyCode::P) {
+            sim.spawn_pedestrian_random();
+        }
+        if is_key_pressed(KeyCode::B) {
+            sim.spawn_bicycle_random();
+        }
+        if is_key_pressed(KeyCode::F) {
+            sim.set_flashing(!sim.is_flashing());
         }
 
-        cars.retain(|car| {
-            car.x > -LANE_WIDTH * 2.0
-                && car.x < WINDOW_WIDTH + LANE_WIDTH * 2.0
-                && car.y > -LANE_WIDTH * 2.0
-                && car.y < WINDOW_HEIGHT + LANE_WIDTH * 2.0
-        });
+        // Clamp long frames so a stall doesn't turn into a burst of ticks.
+        accumulator += get_frame_time().min(MAX_FRAME_TIME);
+        while accumulator >= TICK {
+            sim.step(TICK);
+            accumulator -= TICK;
+        }
+        for err in sim.errors.drain(..) {
+            eprintln!("{err}");
+        }
 
         clear_background(Color::from_rgba(34, 139, 34, 255));
 
-        draw_roads();
-        draw_lane_markings();
+        draw_roads(&sim.scenario);
+        draw_lane_markings(&sim.scenario);
+        sim.zone.draw(&sim.scenario);
 
-        let now = get_time();
-
-        if now - last_change >= 1.0 {
-            if all_red_start.is_none() {
-                for light in &mut lights {
-                    light.green = false;
-                }
-                all_red_start = Some(now);
-            } else if now - all_red_start.unwrap() >= 0.5 {
-                // let random_index = rand::gen_range(0, lights.len());
-                // lights[random_index].update();
-                println!(
-                    "{}, {}, {}, {}",
-                    north_count, south_count, east_count, west_count
-                );
-                if north_count >= south_count && north_count > east_count && north_count > west_count
-                {
-                    lights[1].update();
-                } else if south_count >= north_count
-                    && south_count > east_count
-                    && south_count > west_count
-                {
-                    lights[0].update();
-                } else if east_count >= north_count
-                    && east_count > south_count
-                    && east_count > west_count
-                {
-                    lights[3].update();
-                } else if west_count >= north_count
-                    && west_count > south_count
-                    && west_count > east_count
-                {
-                    lights[2].update();
-                } else {
-                    let random_index = rand::gen_range(0, lights.len());
-                    lights[random_index].update();
-                }
-
-                last_change = now;
-                all_red_start = None;
-            }
+        for detector in &sim.detectors {
+            detector.draw(&sim.scenario);
         }
 
-        for i in 0..cars.len() {
-            let mut car_can_move = true;
-
-            for light in &lights {
-                match (light.direction, cars[i].direction) {
-                    (Direction::South, Direction::North) => {
-                        if cars[i].y == light.y {
-                            if !light.green {
-                                cars[i].check_move = false;
-                                car_can_move = false;
-                            }
-                        }
-                    }
-                    (Direction::North, Direction::South) => {
-                        if cars[i].y == light.y {
-                            if !light.green {
-                                cars[i].check_move = false;
-                                car_can_move = false;
-                            }
-                        }
-                    }
-                    (Direction::East, Direction::West) => {
-                        if cars[i].x == light.x {
-                            if !light.green {
-                                cars[i].check_move = false;
-                                car_can_move = false;
-                            }
-                        }
-                    }
-                    (Direction::West, Direction::East) => {
-                        if cars[i].x + 10.0 == light.x {
-                            if !light.green {
-                                cars[i].check_move = false;
-                                car_can_move = false;
-                            }
-                        }
-                    }
-                    _ => {}
-                }
-            }
-
-            for j in 0..cars.len() {
-                if i == j {
-                    continue;
-                }
-                let front_car = &cars[j];
-
-                match cars[i].direction {
-                    Direction::North => {
-                        if front_car.direction == Direction::North
-                            && front_car.y < cars[i].y
-                            && cars[i].y - front_car.y < 90.0
-                            && front_car.y >= lights[1].y
-                        {
-                            car_can_move = false;
-                        }
-                    }
-                    Direction::South => {
-                        if front_car.direction == Direction::South
-                            && front_car.y > cars[i].y
-                            && front_car.y - cars[i].y < 90.0
-                            && front_car.y <= lights[0].y
-                        {
-                            car_can_move = false;
-                        }
-                    }
-                    Direction::East => {
-                        if front_car.direction == Direction::East
-                            && front_car.x > cars[i].x
-                            && front_car.x - cars[i].x < 90.0
-                            && front_car.x <= lights[3].x
-                        {
-                            car_can_move = false;
-                        }
-                    }
-                    Direction::West => {
-                        if front_car.direction == Direction::West
-                            && front_car.x < cars[i].x
-                            && cars[i].x - front_car.x < 90.0
-                            && front_car.x >= lights[2].x
-                        {
-                            car_can_move = false;
-                        }
-                    }
-                }
-            }
-
-            if car_can_move {
-                cars[i].update();
-            }
+        for car in &sim.cars {
+            car.draw();
         }
 
-        for car in &mut cars {
-            let mut should_decrement = false;
-
-            match car.direction {
-                Direction::North => match car.turn {
-                    Turn::Left => {
-                        if car.x == WINDOW_WIDTH / 2.0 && car.y == WINDOW_HEIGHT / 2.0 - LANE_WIDTH && car.check == false
-                        {
-                            should_decrement = true;
-                            car.update_direction();
-                        }
-                    }
-                    Turn::Right => {
-                        if car.x == WINDOW_WIDTH / 2.0 && car.y == WINDOW_HEIGHT / 2.0 && car.check == false{
-                            should_decrement = true;
-                            car.update_direction();
-                        }
-                    }
-                    Turn::Front => {
-                        if car.y <= WINDOW_HEIGHT / 2.0 - LANE_WIDTH && car.check == false{
-                            should_decrement = true;
-                            car.update_direction();
-                        }
-                    }
-                },
-                Direction::South => match car.turn {
-                    Turn::Left => {
-                        if car.x == WINDOW_WIDTH / 2.0 - LANE_WIDTH && car.y == WINDOW_HEIGHT / 2.0 && car.check == false
-                        {
-                            should_decrement = true;
-                            car.update_direction();
-                        }
-                    }
-                    Turn::Right => {
-                        if car.x == WINDOW_WIDTH / 2.0 - LANE_WIDTH
-                            && car.y == WINDOW_HEIGHT / 2.0 - LANE_WIDTH && car.check == false
-                        {
-                            should_decrement = true;
-                            car.update_direction();
-                        }
-                    }
-                    Turn::Front => {
-                        if car.y >= WINDOW_HEIGHT / 2.0 + LANE_WIDTH && car.check == false{
-                            should_decrement = true;
-                            car.update_direction();
-                        }
-                    }
-                },
-                Direction::East => match car.turn {
-                    Turn::Left => {
-                        if car.x == WINDOW_WIDTH / 2.0 && car.y == WINDOW_HEIGHT / 2.0 && car.check == false{
-                            should_decrement = true;
-                            car.update_direction();
-                        }
-                    }
-                    Turn::Right => {
-                        if car.x == WINDOW_WIDTH / 2.0 - LANE_WIDTH && car.y == WINDOW_HEIGHT / 2.0 && car.check == false
-                        {
-                            should_decrement = true;
-                            car.update_direction();
-                        }
-                    }
-                    Turn::Front => {
-                        if car.x >= WINDOW_WIDTH / 2.0 + LANE_WIDTH && car.check == false{
-                            should_decrement = true;
-                            car.update_direction();
-                        }
-                    }
-                },
-                Direction::West => match car.turn {
-                    Turn::Left => {
-                        if car.x == WINDOW_WIDTH / 2.0 - LANE_WIDTH
-                            && car.y == WINDOW_HEIGHT / 2.0 - LANE_WIDTH && car.check == false
-                        {
-                            should_decrement = true;
-                            car.update_direction();
-                        }
-                    }
-                    Turn::Right => {
-                        if car.x == WINDOW_WIDTH / 2.0 && car.y == WINDOW_HEIGHT / 2.0 - LANE_WIDTH && car.check == false
-                        {
-                            should_decrement = true;
-                            car.update_direction();
-                        }
-                    }
-                    Turn::Front => {
-                        if car.x <= WINDOW_WIDTH / 2.0 - LANE_WIDTH && car.check == false{
-                            should_decrement = true;
-                            car.update_direction();
-                        }
-                    }
-                },
-            };
-
-            if should_decrement {
-                match car.direction {
-                    Direction::North => {
-                        if north_count > 0 {
-                            car.check = true;
-                            north_count -= 1;
-                        }
-                    }
-                    Direction::South => {
-                        if south_count > 0 {
-                            car.check = true;
-                            south_count -= 1;
-                        }
-                    }
-                    Direction::East => {
-                        if east_count > 0 {
-                            car.check = true;
-                            east_count -= 1;
-                        }
-                    }
-                    Direction::West => {
-                        if west_count > 0 {
-                            car.check = true;
-                            west_count -= 1;
-                        }
-                    }
-                }
-            }
-
-            car.draw();
+        for pedestrian in &sim.pedestrians {
+            pedestrian.draw();
         }
 
-        for light in &lights {
+        for light in &sim.lights {
             light.draw();
         }
+        if sim.scenario.control == Control::AllWayStop {
+            draw_stop_signs(&sim.scenario);
+        } else if sim.scenario.pedestrians.enabled {
+            for (signal, leg) in sim.walk_signals.iter().zip(Direction::ALL) {
+                signal.draw(&sim.scenario.crosswalk(leg));
+            }
+        }
 
         next_frame().await;
     }
 }
 
-fn draw_roads() {
-    draw_rectangle(
-        0.0,
-        WINDOW_HEIGHT / 2.0 - LANE_WIDTH,
-        WINDOW_WIDTH,
-        LANE_WIDTH * 2.0,
-        DARKGRAY,
-    );
-
-    draw_rectangle(
-        WINDOW_WIDTH / 2.0 - LANE_WIDTH,
-        0.0,
-        LANE_WIDTH * 2.0,
-        WINDOW_HEIGHT,
-        DARKGRAY,
-    );
-}
-
-fn draw_lane_markings() {
-    let mut start = vec2(WINDOW_WIDTH / 2.0, 0.0);
-    let mut end = vec2(WINDOW_WIDTH / 2.0, WINDOW_HEIGHT);
-    draw_dashed_line(start, end, 15.0, 10.0, 1.0, YELLOW);
-    start = vec2(0.0, WINDOW_HEIGHT / 2.0);
-    end = vec2(WINDOW_WIDTH, WINDOW_HEIGHT / 2.0);
-    draw_dashed_line(start, end, 15.0, 10.0, 1.0, YELLOW);
-    draw_line(
-        0.0,
-        WINDOW_HEIGHT / 2.0 - LANE_WIDTH,
-        WINDOW_WIDTH / 2.0 - LANE_WIDTH,
-        WINDOW_HEIGHT / 2.0 - LANE_WIDTH,
-        1.0,
-        WHITE,
-    );
-    draw_line(
-        0.0,
-        WINDOW_HEIGHT / 2.0 + LANE_WIDTH,
-        WINDOW_WIDTH / 2.0 - LANE_WIDTH,
-        WINDOW_HEIGHT / 2.0 + LANE_WIDTH,
-        1.0,
-        WHITE,
-    );
-    draw_line(
-        WINDOW_WIDTH / 2.0 + LANE_WIDTH,
-        WINDOW_HEIGHT / 2.0 - LANE_WIDTH,
-        WINDOW_WIDTH,
-        WINDOW_HEIGHT / 2.0 - LANE_WIDTH,
-        1.0,
-        WHITE,
-    );
-    draw_line(
-        WINDOW_WIDTH / 2.0 + LANE_WIDTH,
-        WINDOW_HEIGHT / 2.0 + LANE_WIDTH,
-        WINDOW_WIDTH,
-        WINDOW_HEIGHT / 2.0 + LANE_WIDTH,
-        1.0,
-        WHITE,
-    );
-    draw_line(
-        WINDOW_WIDTH / 2.0 - LANE_WIDTH,
-        0.0,
-        WINDOW_WIDTH / 2.0 - LANE_WIDTH,
-        WINDOW_HEIGHT / 2.0 - LANE_WIDTH,
-        1.0,
-        WHITE,
-    );
-    draw_line(
-        WINDOW_WIDTH / 2.0 + LANE_WIDTH,
-        0.0,
-        WINDOW_WIDTH / 2.0 + LANE_WIDTH,
-        WINDOW_HEIGHT / 2.0 - LANE_WIDTH,
-        1.0,
-        WHITE,
-    );
-    draw_line(
-        WINDOW_WIDTH / 2.0 - LANE_WIDTH,
-        WINDOW_HEIGHT / 2.0 + LANE_WIDTH,
-        WINDOW_WIDTH / 2.0 - LANE_WIDTH,
-        WINDOW_HEIGHT,
-        1.0,
-        WHITE,
-    );
-    draw_line(
-        WINDOW_WIDTH / 2.0 + LANE_WIDTH,
-        WINDOW_HEIGHT / 2.0 + LANE_WIDTH,
-        WINDOW_WIDTH / 2.0 + LANE_WIDTH,
-        WINDOW_HEIGHT,
-        1.0,
-        WHITE,
-    );
-}
-
-fn draw_dashed_line(
-    start: Vec2,
-    end: Vec2,
-    dash_length: f32,
-    gap_length: f32,
-    thickness: f32,
-    color: Color,
-) {
-    let direction = (end - start).normalize();
-    let total_length = start.distance(end);
-
-    let mut current_pos = start;
-    let mut distance_travelled = 0.0;
-
-    while distance_travelled < total_length {
-        let segment_end =
-            current_pos + direction * dash_length.min(total_length - distance_travelled);
-        draw_line(
-            current_pos.x,
-            current_pos.y,
-            segment_end.x,
-            segment_end.y,
-            thickness,
-            color,
-        );
-
-        current_pos = segment_end + direction * gap_length;
-        distance_travelled += dash_length + gap_length;
+fn arg_value(name: &str) -> Option<String> {
+    let mut args = env::args().skip_while(|arg| arg != name);
+    args.next()?;
+    let value = args.next();
+    if value.is_none() {
+        eprintln!("{name} expects a value");
     }
+    value
 }
 
-fn can_spawn(cars: &Vec<Car>, lane_pos: f32, min_distance: f32, dir_x: f32, dir_y: f32) -> bool {
-    for car in cars {
-        if dir_y == 0.0 {
-            if (car.y - lane_pos).abs() < 1.0 {
-                if dir_x > 0.0 && car.x < min_distance {
-                    return false;
-                }
-                if dir_x < 0.0 && car.x > WINDOW_WIDTH - min_distance {
-                    return false;
-                }
-            }
-        } else if dir_x == 0.0 {
-            if (car.x - lane_pos).abs() < 1.0 {
-                if dir_y > 0.0 && car.y < min_distance {
-                    return false;
-                }
-                if dir_y < 0.0 && car.y > WINDOW_HEIGHT - min_distance {
-                    return false;
-                }
-            }
+fn seed_from_args() -> Option<u64> {
+    let value = arg_value("--seed")?;
+    match value.parse() {
+        Ok(seed) => Some(seed),
+        Err(_) => {
+            eprintln!("--seed expects an unsigned integer");
+            None
         }
     }
-    true
 }
@@ -0,0 +1,258 @@
+use crate::app::Direction;
+#[cfg(feature = "gui")]
+use macroquad::prelude::*;
+
+// How far from the kerb pedestrians stand on the pavement, waiting to cross
+// or having crossed: back far enough for long vehicles cutting the corner
+// to miss them.
+pub const PAVEMENT: f32 = 30.0;
+// Radius of a pedestrian's footprint.
+pub const RADIUS: f32 = 5.0;
+
+/// The crosswalk over one leg, the arm of the road lying in that direction
+/// from the box. Positions are projections onto `leg` and `leg.right()`,
+/// like a car's along and lateral.
+#[derive(Debug, Clone, Copy)]
+pub struct Crosswalk {
+    pub leg: Direction,
+    // Edges along the leg, nearest the box first.
+    pub near: f32,
+    pub far: f32,
+    // The kerbs it runs between, across the leg.
+    pub start: f32,
+    pub end: f32,
+}
+
+impl Crosswalk {
+    pub fn point(&self, along: f32, across: f32) -> (f32, f32) {
+        let (vx, vy) = self.leg.vector();
+        let (rx, ry) = self.leg.right().vector();
+        (vx * along + rx * across, vy * along + ry * across)
+    }
+
+    /// Distance from where pedestrians wait to the kerb on the far side.
+    pub fn length(&self) -> f32 {
+        self.end - self.start + PAVEMENT
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalkState {
+    DontWalk,
+    Walk,
+    // Anyone already crossing finishes; nobody new sets off.
+    FlashingDontWalk,
+}
+
+/// The pedestrian signal for one crosswalk, with a head at either end.
+#[derive(Debug, Clone, Copy)]
+pub struct WalkSignal {
+    pub state: WalkState,
+    // Simulation time the current state began.
+    pub since: f64,
+}
+
+impl Default for WalkSignal {
+    fn default() -> Self {
+        Self {
+            state: WalkState::DontWalk,
+            since: 0.0,
+        }
+    }
+}
+
+impl WalkSignal {
+    pub fn set(&mut self, state: WalkState, now: f64) {
+        if self.state != state {
+            self.state = state;
+            self.since = now;
+        }
+    }
+
+    /// Starts the walk time over while nobody waiting can use the walk.
+    pub fn hold(&mut self, now: f64) {
+        if self.state == WalkState::Walk {
+            self.since = now;
+        }
+    }
+
+    /// Shows walk if `call`ed while on don't walk, keeps it for `walk_time`
+    /// seconds, then flashes don't walk for `clearance`, or until nobody is
+    /// left `crossing`.
+    pub fn update(&mut self, call: bool, crossing: bool, walk_time: f64, clearance: f64, now: f64) {
+        let elapsed = now - self.since;
+        match self.state {
+            WalkState::DontWalk if call => self.set(WalkState::Walk, now),
+            WalkState::Walk if elapsed >= walk_time => self.set(WalkState::FlashingDontWalk, now),
+            WalkState::FlashingDontWalk if elapsed >= clearance || !crossing => {
+                self.set(WalkState::DontWalk, now)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The way a pedestrian crosses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Route {
+    // Over the crosswalk on a leg.
+    Leg(Direction),
+    // Through the box to the opposite corner, during a scramble.
+    Diagonal,
+}
+
+/// Someone crossing from the corner they spawned at to another, in a
+/// straight line.
+#[derive(Debug, Clone)]
+pub struct Pedestrian {
+    pub id: u32,
+    pub route: Route,
+    // Where they wait on the pavement, and where they leave it again.
+    pub from: (f32, f32),
+    pub to: (f32, f32),
+    pub walked: f32,
+    pub speed: f32,
+    pub spawned_at: f64,
+    // When they stepped off the kerb; `None` while waiting for a walk.
+    pub started_at: Option<f64>,
+}
+
+impl Pedestrian {
+    pub fn new(
+        id: u32,
+        route: Route,
+        from: (f32, f32),
+        to: (f32, f32),
+        speed: f32,
+        now: f64,
+    ) -> Self {
+        Self {
+            id,
+            route,
+            from,
+            to,
+            walked: 0.0,
+            speed,
+            spawned_at: now,
+            started_at: None,
+        }
+    }
+
+    /// Someone crossing the crosswalk on its leg towards `heading`, either
+    /// `leg.right()` or `leg.left()`. `lane` places them across the
+    /// crosswalk's width, 0 at the near edge and 1 at the far one.
+    pub fn across(
+        id: u32,
+        crosswalk: &Crosswalk,
+        heading: Direction,
+        lane: f32,
+        speed: f32,
+        now: f64,
+    ) -> Self {
+        let width = crosswalk.far - crosswalk.near - 2.0 * RADIUS;
+        let along = crosswalk.near + RADIUS + width * lane;
+        let (mut from, mut to) = (crosswalk.start - PAVEMENT, crosswalk.end + PAVEMENT);
+        if heading != crosswalk.leg.right() {
+            (from, to) = (to, from);
+        }
+        let (from, to) = (crosswalk.point(along, from), crosswalk.point(along, to));
+        Self::new(id, Route::Leg(crosswalk.leg), from, to, speed, now)
+    }
+
+    pub fn is_crossing(&self) -> bool {
+        self.started_at.is_some()
+    }
+
+    pub fn length(&self) -> f32 {
+        (self.to.0 - self.from.0).hypot(self.to.1 - self.from.1)
+    }
+
+    pub fn position(&self) -> (f32, f32) {
+        self.position_at(self.walked)
+    }
+
+    /// Where they are once they have walked `walked` from where they waited.
+    pub fn position_at(&self, walked: f32) -> (f32, f32) {
+        let t = walked.min(self.length()) / self.length();
+        (
+            self.from.0 + (self.to.0 - self.from.0) * t,
+            self.from.1 + (self.to.1 - self.from.1) * t,
+        )
+    }
+
+    pub fn walk(&mut self, distance: f32) {
+        self.walked = (self.walked + distance).min(self.length());
+    }
+
+    /// Whether they have reached the pavement on the far side.
+    pub fn has_crossed(&self) -> bool {
+        self.walked >= self.length()
+    }
+
+    /// The corners of a square around their footprint, for collision checks.
+    pub fn corners(&self) -> [(f32, f32); 4] {
+        self.corners_at(self.walked)
+    }
+
+    /// The corners of their footprint once they have walked `walked`.
+    pub fn corners_at(&self, walked: f32) -> [(f32, f32); 4] {
+        let (x, y) = self.position_at(walked);
+        [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+            .map(|(a, b)| (x + a * RADIUS, y + b * RADIUS))
+    }
+
+    /// Their footprints a radius apart from where they are to the end of
+    /// their way, covering all the ground they still have to walk.
+    pub fn way_ahead(&self) -> impl Iterator<Item = [(f32, f32); 4]> + '_ {
+        let steps = ((self.length() - self.walked) / RADIUS).ceil() as usize;
+        (0..=steps).map(move |step| self.corners_at(self.walked + step as f32 * RADIUS))
+    }
+}
+
+#[cfg(feature = "gui")]
+impl Pedestrian {
+    pub fn draw(&self) {
+        const SHIRTS: [Color; 4] = [RED, PURPLE, LIME, SKYBLUE];
+        let (x, y) = self.position();
+        draw_circle(x, y, RADIUS, SHIRTS[self.id as usize % SHIRTS.len()]);
+        draw_circle(x, y, RADIUS * 0.5, BROWN);
+        draw_circle_lines(x, y, RADIUS, 1.0, BLACK);
+    }
+}
+
+#[cfg(feature = "gui")]
+impl WalkSignal {
+    /// Draws a head on the pavement at either end of `crosswalk`: a white
+    /// walking figure, or an orange hand, blinking while it flashes.
+    pub fn draw(&self, crosswalk: &Crosswalk) {
+        const SIZE: f32 = 14.0;
+        let blink_on = get_time().fract() < 0.5;
+        let along = crosswalk.far + SIZE / 2.0;
+        for across in [
+            crosswalk.start - PAVEMENT - SIZE / 2.0,
+            crosswalk.end + PAVEMENT + SIZE / 2.0,
+        ] {
+            let (x, y) = crosswalk.point(along, across);
+            let (left, top) = (x - SIZE / 2.0, y - SIZE / 2.0);
+            draw_rectangle(left, top, SIZE, SIZE, BLACK);
+            draw_rectangle_lines(left, top, SIZE, SIZE, 1.0, WHITE);
+            match self.state {
+                WalkState::Walk => {
+                    draw_circle(x, top + 3.5, 1.8, WHITE);
+                    draw_line(x, top + 5.0, x, top + 9.0, 1.5, WHITE);
+                    draw_line(x, top + 9.0, x - 2.5, top + 12.5, 1.5, WHITE);
+                    draw_line(x, top + 9.0, x + 2.5, top + 12.5, 1.5, WHITE);
+                    draw_line(x - 2.5, top + 7.0, x + 2.5, top + 6.0, 1.5, WHITE);
+                }
+                WalkState::FlashingDontWalk if !blink_on => {}
+                WalkState::DontWalk | WalkState::FlashingDontWalk => {
+                    draw_rectangle(x - 3.0, y - 2.0, 6.0, 6.0, ORANGE);
+                    for i in 0..4 {
+                        let fx = x - 3.0 + 1.5 * i as f32;
+                        draw_line(fx + 0.75, y - 5.0, fx + 0.75, y - 2.0, 1.2, ORANGE);
+                    }
+                }
+            }
+        }
+    }
+}
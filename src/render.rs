@@ -0,0 +1,280 @@
+use crate::app::{Direction, Turn};
+use crate::scenario::Scenario;
+use macroquad::prelude::*;
+
+pub fn draw_roads(scenario: &Scenario) {
+    let (width, height) = (scenario.window.width, scenario.window.height);
+    let (left, top, right, bottom) = scenario.intersection_box();
+    draw_rectangle(0.0, top, width, bottom - top, DARKGRAY);
+    draw_rectangle(left, 0.0, right - left, height, DARKGRAY);
+}
+
+pub fn draw_lane_markings(scenario: &Scenario) {
+    let (width, height) = (scenario.window.width, scenario.window.height);
+    let (cx, cy) = scenario.center();
+    if scenario.bicycles.enabled {
+        for direction in Direction::ALL {
+            draw_bike_lane(scenario, direction);
+        }
+    }
+    draw_dashed_line(vec2(cx, 0.0), vec2(cx, height), 15.0, 10.0, 1.0, YELLOW);
+    draw_dashed_line(vec2(0.0, cy), vec2(width, cy), 15.0, 10.0, 1.0, YELLOW);
+
+    for direction in Direction::ALL {
+        draw_carriageway(scenario, direction);
+        draw_stop_line(scenario, direction);
+        draw_arrows(scenario, direction);
+        if scenario.pedestrians.enabled {
+            draw_crosswalk(scenario, direction);
+        }
+    }
+    // The diagonals walked in a scramble, corner to corner through the box.
+    if scenario.pedestrians.scramble.is_enabled() {
+        for corner in [Direction::North, Direction::East] {
+            let (x, y) = scenario.box_corner(corner);
+            let (ox, oy) = scenario.box_corner(corner.opposite());
+            draw_dashed_line(vec2(x, y), vec2(ox, oy), 8.0, 6.0, 3.0, WHITE);
+        }
+    }
+}
+
+/// A stop sign for every approach, where its signal head would stand.
+pub fn draw_stop_signs(scenario: &Scenario) {
+    const RADIUS: f32 = 18.0;
+    for direction in Direction::ALL {
+        let (x, y) = scenario.stop_sign_position(direction);
+        draw_poly(x, y, 8, RADIUS, 22.5, RED);
+        draw_poly_lines(x, y, 8, RADIUS, 22.5, 2.0, WHITE);
+        let size = measure_text("STOP", None, 14, 1.0);
+        draw_text(
+            "STOP",
+            x - size.width / 2.0,
+            y + size.height / 2.0,
+            14.0,
+            WHITE,
+        );
+    }
+}
+
+// Zebra stripes over the road on `leg`, between the box and the stop line.
+fn draw_crosswalk(scenario: &Scenario, leg: Direction) {
+    let crosswalk = scenario.crosswalk(leg);
+    let count = ((crosswalk.end - crosswalk.start) / 12.0).floor();
+    let spacing = (crosswalk.end - crosswalk.start) / count;
+    for i in 0..count as usize {
+        let across = crosswalk.start + (i as f32 + 0.5) * spacing;
+        let (start, end) = (
+            point(leg, crosswalk.near, across),
+            point(leg, crosswalk.far, across),
+        );
+        draw_line(start.x, start.y, end.x, end.y, spacing / 2.0, WHITE);
+    }
+}
+
+// Where the road travelling in `direction` starts and ends on screen, and
+// where it leaves the box, as positions along it.
+fn road_span(scenario: &Scenario, direction: Direction) -> (f32, f32, f32) {
+    let (width, height) = (scenario.window.width, scenario.window.height);
+    let (left, top, right, bottom) = scenario.intersection_box();
+    let along = |points: [(f32, f32); 4]| {
+        points
+            .map(|(x, y)| direction.along(x, y))
+            .into_iter()
+            .fold((f32::MAX, f32::MIN), |(lo, hi), a| (lo.min(a), hi.max(a)))
+    };
+    let (enter, leave) = along([(0.0, 0.0), (width, 0.0), (0.0, height), (width, height)]);
+    let (_, box_exit) = along([(left, top), (right, top), (left, bottom), (right, bottom)]);
+    (enter, box_exit, leave)
+}
+
+// Lines between the lanes travelling in `direction`, dashed except where
+// lanes may not be changed before the stop line, and the kerb beyond the
+// outermost one, or the bike lane and then the kerb, on both legs of the
+// road.
+fn draw_carriageway(scenario: &Scenario, direction: Direction) {
+    let (enter, box_exit, leave) = road_span(scenario, direction);
+    let box_entry = scenario.box_entry(direction);
+    let solid = scenario.solid_lines(direction).max(enter);
+
+    let (cx, cy) = scenario.center();
+    let inside = direction.lateral(cx, cy);
+    let lanes = scenario.lanes(direction).len();
+    for k in 1..=lanes {
+        let lateral = inside + k as f32 * scenario.lane_width;
+        let line = |from: f32, to: f32, dashed: bool| {
+            let (start, end) = (
+                point(direction, from, lateral),
+                point(direction, to, lateral),
+            );
+            if dashed {
+                draw_dashed_line(start, end, 15.0, 10.0, 1.0, WHITE);
+            } else {
+                draw_line(start.x, start.y, end.x, end.y, 1.0, WHITE);
+            }
+        };
+        let kerb = k == lanes;
+        line(enter, solid, !kerb);
+        line(solid, box_entry, false);
+        line(box_exit, leave, !kerb);
+    }
+    let bike = scenario.bike_lane_width();
+    if bike > 0.0 {
+        let lateral = inside + lanes as f32 * scenario.lane_width + bike;
+        for (from, to) in [(enter, box_entry), (box_exit, leave)] {
+            let (start, end) = (
+                point(direction, from, lateral),
+                point(direction, to, lateral),
+            );
+            draw_line(start.x, start.y, end.x, end.y, 1.0, WHITE);
+        }
+    }
+}
+
+// The bike lane travelling in `direction` on both legs of the road, with the
+// cyclists' stop line, the advance stop box in front of the other traffic if
+// there is one, and the queue box where cyclists from `direction` wait
+// halfway through a two-stage left.
+fn draw_bike_lane(scenario: &Scenario, direction: Direction) {
+    let paint = Color::new(0.25, 0.5, 0.3, 1.0);
+    let (enter, box_exit, leave) = road_span(scenario, direction);
+    let (cx, cy) = scenario.center();
+    let inside = direction.lateral(cx, cy);
+    let kerb_lane = inside + scenario.lanes(direction).len() as f32 * scenario.lane_width;
+    let kerb = kerb_lane + scenario.bike_lane_width();
+    let box_entry = scenario.box_entry(direction);
+    fill(direction, (enter, box_entry), (kerb_lane, kerb), paint);
+    fill(direction, (box_exit, leave), (kerb_lane, kerb), paint);
+
+    let stop = scenario.bike_stop_line(direction);
+    let (start, end) = (
+        point(direction, stop, kerb_lane),
+        point(direction, stop, kerb),
+    );
+    draw_line(start.x, start.y, end.x, end.y, 3.0, WHITE);
+    let behind = scenario.stop_line(direction);
+    if stop > behind {
+        let tint = Color::new(paint.r, paint.g, paint.b, 0.6);
+        fill(direction, (behind, stop), (inside, kerb), tint);
+    }
+
+    let across = direction.left();
+    let (x, y) = scenario.queue_box(direction);
+    let (depth, width) = scenario.queue_box_size();
+    let (along, lateral) = (across.along(x, y), across.lateral(x, y));
+    let corners = [
+        point(across, along - depth / 2.0, lateral - width / 2.0),
+        point(across, along + depth / 2.0, lateral - width / 2.0),
+        point(across, along + depth / 2.0, lateral + width / 2.0),
+        point(across, along - depth / 2.0, lateral + width / 2.0),
+    ];
+    for i in 0..4 {
+        let (a, b) = (corners[i], corners[(i + 1) % 4]);
+        draw_line(a.x, a.y, b.x, b.y, 1.0, WHITE);
+    }
+    let (tail, tip) = (
+        point(across, along - depth / 4.0, lateral),
+        point(across, along + depth / 4.0, lateral),
+    );
+    draw_line(tail.x, tail.y, tip.x, tip.y, 1.0, WHITE);
+    let side = vec2(tip.y - tail.y, tail.x - tip.x).normalize() * 2.5;
+    draw_triangle(
+        tip + (tip - tail).normalize() * 3.0,
+        tip + side,
+        tip - side,
+        WHITE,
+    );
+}
+
+// Paints the rectangle between two positions along `direction` and two
+// across it.
+fn fill(direction: Direction, (from, to): (f32, f32), (lo, hi): (f32, f32), color: Color) {
+    let (a, b) = (point(direction, from, lo), point(direction, to, hi));
+    let (min, max) = (a.min(b), a.max(b));
+    draw_rectangle(min.x, min.y, max.x - min.x, max.y - min.y, color);
+}
+
+// A solid bar across the inbound lanes where cars heading in `direction` stop.
+fn draw_stop_line(scenario: &Scenario, direction: Direction) {
+    let (cx, cy) = scenario.center();
+    let stop = scenario.stop_line(direction);
+    let inside = direction.lateral(cx, cy);
+    let outside = inside + scenario.lanes(direction).len() as f32 * scenario.lane_width;
+    let (start, end) = (
+        point(direction, stop, inside),
+        point(direction, stop, outside),
+    );
+    draw_line(start.x, start.y, end.x, end.y, 3.0, WHITE);
+}
+
+// An arrow in each lane before the stop line, with a head for every turn
+// the lane may be used for.
+fn draw_arrows(scenario: &Scenario, direction: Direction) {
+    let (cx, cy) = scenario.center();
+    let fork = scenario.stop_line(direction) - 24.0;
+    let inside = direction.lateral(cx, cy);
+    for (i, lane) in scenario.lanes(direction).iter().enumerate() {
+        let lateral = inside + (i as f32 + 0.5) * scenario.lane_width;
+        let tail = point(direction, fork - 18.0, lateral);
+        let stem = point(direction, fork, lateral);
+        draw_line(tail.x, tail.y, stem.x, stem.y, 2.0, WHITE);
+        for turn in Turn::ALL {
+            if !lane.allows(turn) {
+                continue;
+            }
+            let (elbow, tip) = match turn {
+                Turn::Front => (stem, point(direction, fork + 12.0, lateral)),
+                Turn::Left => (
+                    point(direction, fork + 6.0, lateral),
+                    point(direction, fork + 6.0, lateral - 9.0),
+                ),
+                Turn::Right => (
+                    point(direction, fork + 6.0, lateral),
+                    point(direction, fork + 6.0, lateral + 9.0),
+                ),
+            };
+            draw_line(stem.x, stem.y, elbow.x, elbow.y, 2.0, WHITE);
+            draw_line(elbow.x, elbow.y, tip.x, tip.y, 2.0, WHITE);
+            let ahead = (tip - elbow).normalize();
+            let across = vec2(-ahead.y, ahead.x) * 4.0;
+            draw_triangle(tip + ahead * 5.0, tip + across, tip - across, WHITE);
+        }
+    }
+}
+
+// The screen point at `along` and `lateral` in the frame of `direction`.
+fn point(direction: Direction, along: f32, lateral: f32) -> Vec2 {
+    let (vx, vy) = direction.vector();
+    let (rx, ry) = direction.right().vector();
+    vec2(vx * along + rx * lateral, vy * along + ry * lateral)
+}
+
+fn draw_dashed_line(
+    start: Vec2,
+    end: Vec2,
+    dash_length: f32,
+    gap_length: f32,
+    thickness: f32,
+    color: Color,
+) {
+    let direction = (end - start).normalize();
+    let total_length = start.distance(end);
+
+    let mut current_pos = start;
+    let mut distance_travelled = 0.0;
+
+    while distance_travelled < total_length {
+        let segment_end =
+            current_pos + direction * dash_length.min(total_length - distance_travelled);
+        draw_line(
+            current_pos.x,
+            current_pos.y,
+            segment_end.x,
+            segment_end.y,
+            thickness,
+            color,
+        );
+
+        current_pos = segment_end + direction * gap_length;
+        distance_travelled += dash_length + gap_length;
+    }
+}
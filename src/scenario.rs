@@ -0,0 +1,1336 @@
+use crate::app::{Direction, Turn, TurnArc, VehicleClass};
+use crate::controller::{ControllerKind, GreenLimits, Movement, Phase, PhasePlan, PhaseSpec};
+use crate::detector::DetectorSpec;
+use crate::pedestrian::{self, Crosswalk};
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Intersection geometry and vehicle parameters, loaded from a TOML or JSON
+/// scenario file. Every field has a default matching the built-in crossing,
+/// so a file only needs to list what it changes.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scenario {
+    pub window: Window,
+    pub lane_width: f32,
+    pub car: VehicleSpec,
+    pub vehicles: Vehicles,
+    // Bumper-to-bumper distance kept to the vehicle in front.
+    pub safe_gap: f32,
+    // Seconds of clear traffic a permissive left turner, or a right turner
+    // on red, needs before crossing or joining it.
+    pub critical_gap: f32,
+    pub control: Control,
+    pub approach: Approaches,
+    pub lane_change: LaneChanging,
+    pub pedestrians: Pedestrians,
+    pub bicycles: Bicycles,
+    pub signals: Signals,
+    pub detectors: Vec<DetectorSpec>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Window {
+    pub width: f32,
+    pub height: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VehicleSpec {
+    pub length: f32,
+    pub width: f32,
+    // Desired speed, in pixels per second of simulation time.
+    pub speed: f32,
+    // Maximum acceleration and comfortable braking, in pixels per second
+    // squared.
+    pub accel: f32,
+    pub decel: f32,
+    // Seconds of time gap a driver keeps to the vehicle in front.
+    pub headway: f32,
+}
+
+/// The other vehicle classes and how often each one spawns. A class's table
+/// only needs the fields that differ from that class's defaults.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Vehicles {
+    pub mix: ClassMix,
+    #[serde(deserialize_with = "truck")]
+    pub truck: VehicleSpec,
+    #[serde(deserialize_with = "bus")]
+    pub bus: VehicleSpec,
+    #[serde(deserialize_with = "motorcycle")]
+    pub motorcycle: VehicleSpec,
+    // Spawned on demand only, never by the mix.
+    #[serde(deserialize_with = "emergency")]
+    pub emergency: VehicleSpec,
+    // Spawned on their own, and only with bike lanes.
+    #[serde(deserialize_with = "bicycle")]
+    pub bicycle: VehicleSpec,
+}
+
+/// Relative shares of each class among spawned vehicles.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClassMix {
+    pub car: f32,
+    pub truck: f32,
+    pub bus: f32,
+    pub motorcycle: f32,
+}
+
+impl ClassMix {
+    pub fn share(&self, class: VehicleClass) -> f32 {
+        match class {
+            VehicleClass::Car => self.car,
+            VehicleClass::Truck => self.truck,
+            VehicleClass::Bus => self.bus,
+            VehicleClass::Motorcycle => self.motorcycle,
+            VehicleClass::Emergency | VehicleClass::Bicycle => 0.0,
+        }
+    }
+}
+
+// A class's table as written, laid over that class's defaults.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VehicleFields {
+    length: Option<f32>,
+    width: Option<f32>,
+    speed: Option<f32>,
+    accel: Option<f32>,
+    decel: Option<f32>,
+    headway: Option<f32>,
+}
+
+fn class_spec<'de, D: Deserializer<'de>>(
+    deserializer: D,
+    base: VehicleSpec,
+) -> Result<VehicleSpec, D::Error> {
+    let fields = VehicleFields::deserialize(deserializer)?;
+    Ok(VehicleSpec {
+        length: fields.length.unwrap_or(base.length),
+        width: fields.width.unwrap_or(base.width),
+        speed: fields.speed.unwrap_or(base.speed),
+        accel: fields.accel.unwrap_or(base.accel),
+        decel: fields.decel.unwrap_or(base.decel),
+        headway: fields.headway.unwrap_or(base.headway),
+    })
+}
+
+fn truck<'de, D: Deserializer<'de>>(deserializer: D) -> Result<VehicleSpec, D::Error> {
+    class_spec(deserializer, VehicleSpec::truck())
+}
+
+fn bus<'de, D: Deserializer<'de>>(deserializer: D) -> Result<VehicleSpec, D::Error> {
+    class_spec(deserializer, VehicleSpec::bus())
+}
+
+fn motorcycle<'de, D: Deserializer<'de>>(deserializer: D) -> Result<VehicleSpec, D::Error> {
+    class_spec(deserializer, VehicleSpec::motorcycle())
+}
+
+fn emergency<'de, D: Deserializer<'de>>(deserializer: D) -> Result<VehicleSpec, D::Error> {
+    class_spec(deserializer, VehicleSpec::emergency())
+}
+
+fn bicycle<'de, D: Deserializer<'de>>(deserializer: D) -> Result<VehicleSpec, D::Error> {
+    class_spec(deserializer, VehicleSpec::bicycle())
+}
+
+/// How vehicles change lanes on approaches with more than one, by MOBIL:
+/// a change is made if it is safe for the vehicle that ends up behind and
+/// gains more acceleration than it costs, counting the other vehicles
+/// affected at `politeness`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LaneChanging {
+    pub politeness: f32,
+    // Net gain in acceleration, in pixels per second squared, a change has to
+    // be worth.
+    pub threshold: f32,
+    // Extra gain for moving towards a lane marked for the vehicle's turn, or
+    // cost of leaving one.
+    pub bias: f32,
+    // Hardest braking a change may force on the new follower.
+    pub safe_decel: f32,
+    // Road driven while moving across.
+    pub distance: f32,
+    // Length of the solid lane lines before the stop line, where lanes may
+    // no longer be changed.
+    pub solid: f32,
+}
+
+/// Crosswalks on every leg and the pedestrians who use them. Off unless
+/// enabled; with crosswalks the stop lines move back behind them.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Pedestrians {
+    pub enabled: bool,
+    // Width of a crosswalk, along the road.
+    pub width: f32,
+    // Walking speed, in pixels per second.
+    pub speed: f32,
+    // Seconds of steady walk; the flashing don't walk after it lasts as long
+    // as crossing takes at `speed`.
+    pub walk_time: f32,
+    // Seconds a pedestrian may wait before their crosswalk is forced a walk.
+    pub max_wait: f32,
+    pub scramble: Scramble,
+}
+
+/// When to run the scramble, an all-pedestrian phase with every vehicle
+/// signal red in which pedestrians may also cross diagonally. Never unless
+/// one of the two is set.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scramble {
+    // Every this many signal cycles, a cycle being as many greens as there
+    // are phases; 0 never on a schedule.
+    pub every: u32,
+    // Whenever someone is waiting to cross diagonally, as if they had
+    // pushed the button.
+    pub on_demand: bool,
+}
+
+impl Scramble {
+    pub fn is_enabled(&self) -> bool {
+        self.every > 0 || self.on_demand
+    }
+}
+
+/// A bike lane along the kerb of every approach and exit, and the cyclists
+/// who ride in them. Off unless enabled; with bike lanes the roads widen by
+/// one each side and the stop lines move back behind the queue boxes where
+/// cyclists wait halfway through a two-stage left turn.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Bicycles {
+    pub enabled: bool,
+    pub lane_width: f32,
+    // Depth of the advance stop box between the stop line and the one for
+    // cyclists ahead of it; 0 for none.
+    pub advance_stop: f32,
+}
+
+/// How traffic through the intersection is controlled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Control {
+    // Traffic lights, run as set out in `[signals]`.
+    #[default]
+    Signals,
+    // No lights but a stop sign on every approach: vehicles come to a full
+    // stop at the line and go in the order they stopped.
+    AllWayStop,
+}
+
+impl Control {
+    pub const ALL: [Control; 2] = [Control::Signals, Control::AllWayStop];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Control::Signals => "signals",
+            Control::AllWayStop => "all-way-stop",
+        }
+    }
+}
+
+impl FromStr for Control {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Control::ALL
+            .into_iter()
+            .find(|control| control.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Control::ALL.iter().map(|c| c.name()).collect();
+                format!(
+                    "unknown control `{s}`, expected one of {}",
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Signals {
+    pub controller: ControllerKind,
+    // Seconds between controller decisions while a phase is green.
+    pub green_time: f32,
+    pub amber_time: f32,
+    // Seconds every light stays red between two different phases.
+    pub all_red_time: f32,
+    // Seconds of red-and-amber before green; 0 skips it.
+    pub red_amber_time: f32,
+    // Actuated control: a green is extended while cars keep arriving over
+    // the passage loops less than this many seconds apart.
+    pub gap: f32,
+    pub plan: PhasePlan,
+    // Explicit phases; when present they replace `plan`.
+    pub phases: Vec<PhaseSpec>,
+}
+
+/// Per-approach settings, keyed by the direction cars travel in.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Approaches {
+    pub north: Approach,
+    pub south: Approach,
+    pub east: Approach,
+    pub west: Approach,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Approach {
+    // Distance of the stop line back from the edge of the intersection box.
+    pub stop_line_offset: f32,
+    // Top-left corner of the signal head; placed beside the stop line if unset.
+    pub light: Option<[f32; 2]>,
+    // Seconds; see `GreenLimits`.
+    pub min_green: f32,
+    pub max_green: f32,
+    pub max_wait: f32,
+    // Let right turners go on red after a full stop when the way is clear.
+    pub right_turn_on_red: bool,
+    // Inbound lanes from the centre line out to the kerb, each with the
+    // turns it may be used for. Cars leave on as many lanes as arrive in
+    // their direction of travel.
+    pub lanes: Vec<LaneUse>,
+}
+
+/// The turns a lane may be used for, as painted on its arrows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LaneUse {
+    Left,
+    Through,
+    Right,
+    LeftThrough,
+    ThroughRight,
+    All,
+}
+
+impl LaneUse {
+    pub fn allows(self, turn: Turn) -> bool {
+        match self {
+            LaneUse::Left => turn == Turn::Left,
+            LaneUse::Through => turn == Turn::Front,
+            LaneUse::Right => turn == Turn::Right,
+            LaneUse::LeftThrough => turn != Turn::Right,
+            LaneUse::ThroughRight => turn != Turn::Left,
+            LaneUse::All => true,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(std::io::Error),
+    Parse(String),
+    Invalid { field: String, message: String },
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Io(err) => write!(f, "cannot read scenario: {err}"),
+            ScenarioError::Parse(err) => write!(f, "cannot parse scenario: {err}"),
+            ScenarioError::Invalid { field, message } => write!(f, "`{field}` {message}"),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Self {
+            window: Window::default(),
+            lane_width: 50.0,
+            car: VehicleSpec::default(),
+            vehicles: Vehicles::default(),
+            safe_gap: 40.0,
+            critical_gap: 1.0,
+            control: Control::default(),
+            approach: Approaches::default(),
+            lane_change: LaneChanging::default(),
+            pedestrians: Pedestrians::default(),
+            bicycles: Bicycles::default(),
+            signals: Signals::default(),
+            detectors: DetectorSpec::standard(),
+        }
+    }
+}
+
+impl Default for Signals {
+    fn default() -> Self {
+        Self {
+            controller: ControllerKind::default(),
+            green_time: 1.0,
+            amber_time: 0.5,
+            all_red_time: 0.5,
+            red_amber_time: 0.0,
+            gap: 1.0,
+            plan: PhasePlan::default(),
+            phases: Vec::new(),
+        }
+    }
+}
+
+impl Default for LaneChanging {
+    fn default() -> Self {
+        Self {
+            politeness: 0.3,
+            threshold: 20.0,
+            bias: 2000.0,
+            safe_decel: 800.0,
+            distance: 100.0,
+            solid: 60.0,
+        }
+    }
+}
+
+impl Default for Pedestrians {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            width: 24.0,
+            speed: 40.0,
+            walk_time: 3.0,
+            max_wait: 30.0,
+            scramble: Scramble::default(),
+        }
+    }
+}
+
+impl Default for Bicycles {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            lane_width: 16.0,
+            advance_stop: 0.0,
+        }
+    }
+}
+
+impl Default for Window {
+    fn default() -> Self {
+        Self {
+            width: 800.0,
+            height: 600.0,
+        }
+    }
+}
+
+impl Default for VehicleSpec {
+    fn default() -> Self {
+        Self {
+            length: 44.0,
+            width: 24.0,
+            speed: 320.0,
+            accel: 400.0,
+            decel: 1280.0,
+            headway: 0.5,
+        }
+    }
+}
+
+impl VehicleSpec {
+    pub fn truck() -> Self {
+        Self {
+            length: 90.0,
+            width: 28.0,
+            speed: 240.0,
+            accel: 200.0,
+            decel: 800.0,
+            headway: 0.8,
+        }
+    }
+
+    pub fn bus() -> Self {
+        Self {
+            length: 80.0,
+            width: 28.0,
+            speed: 260.0,
+            accel: 240.0,
+            decel: 900.0,
+            headway: 0.7,
+        }
+    }
+
+    pub fn motorcycle() -> Self {
+        Self {
+            length: 26.0,
+            width: 12.0,
+            speed: 340.0,
+            accel: 600.0,
+            decel: 1400.0,
+            headway: 0.4,
+        }
+    }
+
+    pub fn emergency() -> Self {
+        Self {
+            length: 52.0,
+            width: 26.0,
+            speed: 400.0,
+            accel: 450.0,
+            decel: 1280.0,
+            headway: 0.5,
+        }
+    }
+
+    pub fn bicycle() -> Self {
+        Self {
+            length: 18.0,
+            width: 8.0,
+            speed: 110.0,
+            accel: 150.0,
+            decel: 500.0,
+            headway: 0.8,
+        }
+    }
+}
+
+impl Default for Vehicles {
+    fn default() -> Self {
+        Self {
+            mix: ClassMix::default(),
+            truck: VehicleSpec::truck(),
+            bus: VehicleSpec::bus(),
+            motorcycle: VehicleSpec::motorcycle(),
+            emergency: VehicleSpec::emergency(),
+            bicycle: VehicleSpec::bicycle(),
+        }
+    }
+}
+
+impl Default for ClassMix {
+    fn default() -> Self {
+        Self {
+            car: 0.85,
+            truck: 0.07,
+            bus: 0.03,
+            motorcycle: 0.05,
+        }
+    }
+}
+
+impl Default for Approach {
+    fn default() -> Self {
+        Self {
+            stop_line_offset: 0.0,
+            light: None,
+            min_green: 2.0,
+            max_green: 8.0,
+            max_wait: 12.0,
+            right_turn_on_red: false,
+            lanes: vec![LaneUse::All],
+        }
+    }
+}
+
+impl Approach {
+    pub fn limits(&self) -> GreenLimits {
+        GreenLimits {
+            min_green: self.min_green as f64,
+            max_green: self.max_green as f64,
+            max_wait: self.max_wait as f64,
+        }
+    }
+}
+
+impl Approaches {
+    pub fn get(&self, direction: Direction) -> &Approach {
+        match direction {
+            Direction::North => &self.north,
+            Direction::South => &self.south,
+            Direction::East => &self.east,
+            Direction::West => &self.west,
+        }
+    }
+}
+
+fn invalid(field: impl Into<String>, message: impl Into<String>) -> ScenarioError {
+    ScenarioError::Invalid {
+        field: field.into(),
+        message: message.into(),
+    }
+}
+
+// The table a class's fields are set in.
+fn class_table(class: VehicleClass) -> String {
+    match class {
+        VehicleClass::Car => "car".to_string(),
+        _ => format!("vehicles.{}", class.key()),
+    }
+}
+
+fn positive(field: &str, value: f32) -> Result<(), ScenarioError> {
+    if value.is_finite() && value > 0.0 {
+        Ok(())
+    } else {
+        Err(invalid(field, format!("must be positive, got {value}")))
+    }
+}
+
+fn non_negative(field: &str, value: f32) -> Result<(), ScenarioError> {
+    if value.is_finite() && value >= 0.0 {
+        Ok(())
+    } else {
+        Err(invalid(field, format!("must not be negative, got {value}")))
+    }
+}
+
+impl Scenario {
+    /// Reads a scenario from `path`, as JSON if it ends in `.json` and as
+    /// TOML otherwise, and validates it.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ScenarioError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(ScenarioError::Io)?;
+        let scenario: Scenario = if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&text).map_err(|err| ScenarioError::Parse(err.to_string()))?
+        } else {
+            toml::from_str(&text).map_err(|err| ScenarioError::Parse(err.to_string()))?
+        };
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    pub fn validate(&self) -> Result<(), ScenarioError> {
+        positive("window.width", self.window.width)?;
+        positive("window.height", self.window.height)?;
+        positive("lane_width", self.lane_width)?;
+        for class in VehicleClass::ALL {
+            let spec = self.spec(class);
+            let table = class_table(class);
+            positive(&format!("{table}.length"), spec.length)?;
+            positive(&format!("{table}.width"), spec.width)?;
+            positive(&format!("{table}.speed"), spec.speed)?;
+            positive(&format!("{table}.accel"), spec.accel)?;
+            positive(&format!("{table}.decel"), spec.decel)?;
+            non_negative(&format!("{table}.headway"), spec.headway)?;
+            let share = self.vehicles.mix.share(class);
+            non_negative(&format!("vehicles.mix.{}", class.key()), share)?;
+        }
+        let total: f32 = VehicleClass::ALL
+            .map(|class| self.vehicles.mix.share(class))
+            .iter()
+            .sum();
+        positive("vehicles.mix", total)?;
+        non_negative("safe_gap", self.safe_gap)?;
+        non_negative("critical_gap", self.critical_gap)?;
+        let lane_change = &self.lane_change;
+        non_negative("lane_change.politeness", lane_change.politeness)?;
+        non_negative("lane_change.threshold", lane_change.threshold)?;
+        non_negative("lane_change.bias", lane_change.bias)?;
+        positive("lane_change.safe_decel", lane_change.safe_decel)?;
+        positive("lane_change.distance", lane_change.distance)?;
+        non_negative("lane_change.solid", lane_change.solid)?;
+        let pedestrians = &self.pedestrians;
+        positive("pedestrians.width", pedestrians.width)?;
+        positive("pedestrians.speed", pedestrians.speed)?;
+        non_negative("pedestrians.walk_time", pedestrians.walk_time)?;
+        positive("pedestrians.max_wait", pedestrians.max_wait)?;
+        if pedestrians.scramble.is_enabled() && !pedestrians.enabled {
+            return Err(invalid(
+                "pedestrians.scramble",
+                "needs pedestrians.enabled, there are no crosswalks",
+            ));
+        }
+        let bicycles = &self.bicycles;
+        positive("bicycles.lane_width", bicycles.lane_width)?;
+        non_negative("bicycles.advance_stop", bicycles.advance_stop)?;
+        if bicycles.advance_stop > 0.0 && !bicycles.enabled {
+            return Err(invalid(
+                "bicycles.advance_stop",
+                "needs bicycles.enabled, there are no bike lanes",
+            ));
+        }
+        // Cyclists' queue boxes and the diagonals of a scramble are run by
+        // the signals.
+        if self.control == Control::AllWayStop {
+            if bicycles.enabled {
+                return Err(invalid(
+                    "bicycles.enabled",
+                    "needs signals, not an all-way stop",
+                ));
+            }
+            if pedestrians.scramble.is_enabled() {
+                return Err(invalid(
+                    "pedestrians.scramble",
+                    "needs signals, not an all-way stop",
+                ));
+            }
+        }
+        positive("signals.green_time", self.signals.green_time)?;
+        non_negative("signals.amber_time", self.signals.amber_time)?;
+        non_negative("signals.all_red_time", self.signals.all_red_time)?;
+        non_negative("signals.red_amber_time", self.signals.red_amber_time)?;
+        positive("signals.gap", self.signals.gap)?;
+
+        for (i, spec) in self.signals.phases.iter().enumerate() {
+            let field = format!("signals.phases[{i}]");
+            if spec.protected.is_empty() && spec.permissive.is_empty() {
+                return Err(invalid(field, "releases no movements"));
+            }
+            if let Some(m) = spec.protected.iter().find(|m| spec.permissive.contains(m)) {
+                return Err(invalid(
+                    field,
+                    format!("lists {m} as both protected and permissive"),
+                ));
+            }
+        }
+        let phases = self.phases();
+        for approach in Direction::ALL {
+            for turn in Turn::ALL {
+                if !phases.iter().any(|p| p.allows(approach, turn)) {
+                    return Err(invalid(
+                        "signals.phases",
+                        format!("never release {}", Movement { approach, turn }),
+                    ));
+                }
+            }
+        }
+        if self.pedestrians.enabled {
+            for leg in Direction::ALL {
+                if !phases.iter().any(|p| p.walks(leg)) {
+                    return Err(invalid(
+                        "signals.phases",
+                        format!(
+                            "never give a walk across the {} leg: every phase sends \
+                             traffic from it or straight on into it",
+                            direction_key(leg)
+                        ),
+                    ));
+                }
+            }
+        }
+
+        // Every class must fit its lane: cyclists the bike lane, the rest
+        // the traffic lanes.
+        for class in VehicleClass::ALL {
+            let (lane_width, lane_field) = match class {
+                VehicleClass::Bicycle if !bicycles.enabled => continue,
+                VehicleClass::Bicycle => (bicycles.lane_width, "bicycles.lane_width"),
+                _ => (self.lane_width, "lane_width"),
+            };
+            let width = self.spec(class).width;
+            if width > lane_width {
+                return Err(invalid(
+                    format!("{}.width", class_table(class)),
+                    format!("({width}) is wider than {lane_field} ({lane_width})"),
+                ));
+            }
+        }
+
+        // The longest class that can be spawned: any in the mix, emergency
+        // vehicles on demand and cyclists where there are bike lanes.
+        let longest = VehicleClass::ALL
+            .into_iter()
+            .filter(|&class| match class {
+                VehicleClass::Emergency => true,
+                VehicleClass::Bicycle => bicycles.enabled,
+                _ => self.vehicles.mix.share(class) > 0.0,
+            })
+            .max_by(|&a, &b| self.spec(a).length.total_cmp(&self.spec(b).length))
+            .unwrap_or(VehicleClass::Car);
+
+        for direction in Direction::ALL {
+            let name = direction_key(direction);
+            let approach = self.approach.get(direction);
+            let field = format!("approach.{name}.lanes");
+            if approach.lanes.is_empty() || approach.lanes.len() > MAX_LANES {
+                return Err(invalid(
+                    field,
+                    format!(
+                        "must list 1 to {MAX_LANES} lanes, got {}",
+                        approach.lanes.len()
+                    ),
+                ));
+            }
+            for turn in Turn::ALL {
+                if !approach.lanes.iter().any(|lane| lane.allows(turn)) {
+                    let movement = Movement {
+                        approach: direction,
+                        turn,
+                    };
+                    return Err(invalid(field, format!("has no lane for {movement}")));
+                }
+            }
+
+            let field = format!("approach.{name}.stop_line_offset");
+            non_negative(&field, approach.stop_line_offset)?;
+
+            // The stop line and the longest vehicle queued there must fit
+            // between the box and the window edge.
+            let room = self.leg_length(direction) - self.setback(direction);
+            if room < self.spec(longest).length {
+                return Err(invalid(
+                    field,
+                    format!(
+                        "leaves {room} px before the window edge, less than one {}",
+                        longest.key()
+                    ),
+                ));
+            }
+
+            non_negative(&format!("approach.{name}.min_green"), approach.min_green)?;
+            positive(&format!("approach.{name}.max_wait"), approach.max_wait)?;
+            if approach.max_green.is_nan() || approach.max_green < approach.min_green {
+                return Err(invalid(
+                    format!("approach.{name}.max_green"),
+                    format!(
+                        "({}) is shorter than min_green ({})",
+                        approach.max_green, approach.min_green
+                    ),
+                ));
+            }
+
+            if let Some([x, y]) = approach.light {
+                let outside = !(0.0..=self.window.width).contains(&x)
+                    || !(0.0..=self.window.height).contains(&y);
+                if outside {
+                    return Err(invalid(
+                        format!("approach.{name}.light"),
+                        format!("[{x}, {y}] is outside the window"),
+                    ));
+                }
+            }
+        }
+
+        for (i, detector) in self.detectors.iter().enumerate() {
+            non_negative(&format!("detectors[{i}].distance"), detector.distance)?;
+            positive(&format!("detectors[{i}].length"), detector.length)?;
+            let room = self.leg_length(detector.approach) - self.setback(detector.approach);
+            if detector.distance + detector.length > room {
+                return Err(invalid(
+                    format!("detectors[{i}]"),
+                    format!("reaches {room} px past the stop line, off the end of the road"),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn spec(&self, class: VehicleClass) -> &VehicleSpec {
+        match class {
+            VehicleClass::Car => &self.car,
+            VehicleClass::Truck => &self.vehicles.truck,
+            VehicleClass::Bus => &self.vehicles.bus,
+            VehicleClass::Motorcycle => &self.vehicles.motorcycle,
+            VehicleClass::Emergency => &self.vehicles.emergency,
+            VehicleClass::Bicycle => &self.vehicles.bicycle,
+        }
+    }
+
+    pub fn longest_vehicle(&self) -> f32 {
+        VehicleClass::ALL
+            .map(|class| self.spec(class).length)
+            .into_iter()
+            .fold(0.0, f32::max)
+    }
+
+    /// The phases the signal controller chooses between, with pedestrians
+    /// given a walk alongside whatever traffic they can share it with.
+    pub fn phases(&self) -> Vec<Phase> {
+        let phases = if self.signals.phases.is_empty() {
+            self.signals.plan.phases()
+        } else {
+            self.signals.phases.iter().map(PhaseSpec::phase).collect()
+        };
+        if self.pedestrians.enabled {
+            phases
+                .into_iter()
+                .map(Phase::with_concurrent_walks)
+                .collect()
+        } else {
+            phases
+        }
+    }
+
+    pub fn center(&self) -> (f32, f32) {
+        (self.window.width / 2.0, self.window.height / 2.0)
+    }
+
+    /// The lanes cars travelling in `direction` arrive on, from the centre
+    /// line out.
+    pub fn lanes(&self, direction: Direction) -> &[LaneUse] {
+        &self.approach.get(direction).lanes
+    }
+
+    /// Index of the bike lane beside the lanes travelling in `direction`,
+    /// the next one out past the kerb lane.
+    pub fn bike_lane(&self, direction: Direction) -> usize {
+        self.lanes(direction).len()
+    }
+
+    /// Width of the bike lanes; 0 without them.
+    pub fn bike_lane_width(&self) -> f32 {
+        if self.bicycles.enabled {
+            self.bicycles.lane_width
+        } else {
+            0.0
+        }
+    }
+
+    // Width of the carriageway travelling in `direction`, from the centre
+    // line to the kerb.
+    fn carriageway(&self, direction: Direction) -> f32 {
+        self.lanes(direction).len() as f32 * self.lane_width + self.bike_lane_width()
+    }
+
+    /// The intersection box as (left, top, right, bottom): as wide as the
+    /// north-south road and as high as the east-west one.
+    pub fn intersection_box(&self) -> (f32, f32, f32, f32) {
+        let (cx, cy) = self.center();
+        (
+            cx - self.carriageway(Direction::South),
+            cy - self.carriageway(Direction::West),
+            cx + self.carriageway(Direction::North),
+            cy + self.carriageway(Direction::East),
+        )
+    }
+
+    /// Position along `direction` of the edge of the box cars travelling in
+    /// it drive in over.
+    pub fn box_entry(&self, direction: Direction) -> f32 {
+        let (cx, cy) = self.center();
+        direction.along(cx, cy) - self.carriageway(direction.right())
+    }
+
+    // Length of road between the intersection box and the window edge on the
+    // leg cars heading in `direction` arrive from.
+    pub fn leg_length(&self, direction: Direction) -> f32 {
+        let half_extent = match direction {
+            Direction::North | Direction::South => self.window.height / 2.0,
+            Direction::East | Direction::West => self.window.width / 2.0,
+        };
+        half_extent - self.carriageway(direction.right())
+    }
+
+    /// A point on the centre line of a lane travelling in `heading`, lanes
+    /// counted from the centre line of the road out to the bike lane.
+    pub fn lane_point(&self, heading: Direction, lane: usize) -> (f32, f32) {
+        let (cx, cy) = self.center();
+        let (rx, ry) = heading.right().vector();
+        let lanes = self.lanes(heading).len();
+        let offset = if lane < lanes {
+            (lane as f32 + 0.5) * self.lane_width
+        } else {
+            lanes as f32 * self.lane_width + self.bike_lane_width() / 2.0
+        };
+        (cx + rx * offset, cy + ry * offset)
+    }
+
+    /// Position along `direction` of where the solid lane lines before the
+    /// stop line start; lanes can only be changed before it.
+    pub fn solid_lines(&self, direction: Direction) -> f32 {
+        self.stop_line(direction) - self.lane_change.solid
+    }
+
+    /// Position along `direction` of the stop line for cars travelling in it.
+    pub fn stop_line(&self, direction: Direction) -> f32 {
+        self.box_entry(direction) - self.setback(direction)
+    }
+
+    /// Position along `direction` of the stop line for cyclists travelling
+    /// in it, at the front of the advance stop box if there is one.
+    pub fn bike_stop_line(&self, direction: Direction) -> f32 {
+        self.stop_line(direction) + self.advance_stop()
+    }
+
+    // Distance of the stop line back from the box: the approach's offset,
+    // plus room for the queue box, crosswalk and advance stop box in between.
+    fn setback(&self, direction: Direction) -> f32 {
+        let crosswalk = if self.pedestrians.enabled {
+            self.pedestrians.width + 2.0 * CROSSWALK_GAP
+        } else {
+            0.0
+        };
+        self.approach.get(direction).stop_line_offset
+            + self.queue_depth()
+            + crosswalk
+            + self.advance_stop()
+    }
+
+    fn advance_stop(&self) -> f32 {
+        if self.bicycles.enabled {
+            self.bicycles.advance_stop
+        } else {
+            0.0
+        }
+    }
+
+    // Length of road a queue box takes up between the box and the
+    // crosswalk, room for one cyclist; 0 without bike lanes.
+    fn queue_depth(&self) -> f32 {
+        if self.bicycles.enabled {
+            self.queue_box_front() + self.vehicles.bicycle.length + QUEUE_BOX_GAP
+        } else {
+            0.0
+        }
+    }
+
+    // Space between the box and the front of a cyclist waiting in a queue
+    // box. With approaches of different widths the diagonals are not square
+    // to the corners and can cut across a bike lane just off the box, so
+    // the cyclist waits far enough back to keep clear of both.
+    fn queue_box_front(&self) -> f32 {
+        let clearance = pedestrian::RADIUS + self.vehicles.bicycle.width / 2.0;
+        let mut front = QUEUE_BOX_GAP;
+        for direction in Direction::ALL {
+            let across = direction.left();
+            let (x, y) = self.lane_point(across, self.bike_lane(across));
+            let shift = self.box_entry(across) - across.along(x, y);
+            let (vx, vy) = across.vector();
+            let (x, y) = (x + vx * shift, y + vy * shift);
+            for corner in [Direction::North, Direction::East] {
+                let (ax, ay) = self.waiting_spot(corner);
+                let (bx, by) = self.waiting_spot(corner.opposite());
+                let length = (bx - ax).hypot(by - ay);
+                let (nx, ny) = ((ay - by) / length, (bx - ax) / length);
+                // Distance off the diagonal on the side waiting further
+                // back moves the cyclist towards, and how fast it does.
+                let rate = -(vx * nx + vy * ny);
+                let off = ((x - ax) * nx + (y - ay) * ny) * rate.signum();
+                if rate != 0.0 {
+                    front = front.max((clearance - off) / rate.abs());
+                }
+            }
+        }
+        front
+    }
+
+    /// Where a cyclist travelling in `direction` waits halfway through a
+    /// two-stage left turn: in the queue box just off the far corner of the
+    /// box, in the bike lane of the street it turns into.
+    pub fn queue_box(&self, direction: Direction) -> (f32, f32) {
+        let across = direction.left();
+        let (x, y) = self.lane_point(across, self.bike_lane(across));
+        let (vx, vy) = across.vector();
+        let back = self.queue_box_front() + self.vehicles.bicycle.length / 2.0;
+        let shift = self.box_entry(across) - back - across.along(x, y);
+        (x + vx * shift, y + vy * shift)
+    }
+
+    /// Size of a queue box, along the bike lane and across it.
+    pub fn queue_box_size(&self) -> (f32, f32) {
+        (
+            self.vehicles.bicycle.length + 2.0 * QUEUE_BOX_GAP,
+            self.bike_lane_width(),
+        )
+    }
+
+    /// The corner of the box between the legs `corner` and `corner.right()`,
+    /// e.g. the north-east one for north.
+    pub fn box_corner(&self, corner: Direction) -> (f32, f32) {
+        let (cx, cy) = self.center();
+        let (vx, vy) = corner.vector();
+        let (rx, ry) = corner.right().vector();
+        let (along, lateral) = (self.carriageway(corner.left()), self.carriageway(corner));
+        (
+            cx + vx * along + rx * lateral,
+            cy + vy * along + ry * lateral,
+        )
+    }
+
+    /// Where someone waits to cross diagonally from `corner`: on the
+    /// pavement beyond it, clear of both crosswalks.
+    pub fn waiting_spot(&self, corner: Direction) -> (f32, f32) {
+        let (x, y) = self.box_corner(corner);
+        let (vx, vy) = corner.vector();
+        let (rx, ry) = corner.right().vector();
+        (
+            x + (vx + rx) * pedestrian::PAVEMENT,
+            y + (vy + ry) * pedestrian::PAVEMENT,
+        )
+    }
+
+    /// Seconds a change of phase may take to bring up a walk: letting the
+    /// longest walk showing run out and clear, then the signals' own
+    /// clearance.
+    pub fn walk_lead(&self) -> f64 {
+        let pedestrians = &self.pedestrians;
+        let crossing = Direction::ALL
+            .map(|leg| self.crosswalk(leg).length() / pedestrians.speed)
+            .into_iter()
+            .fold(0.0, f32::max);
+        let signals = &self.signals;
+        (pedestrians.walk_time
+            + crossing
+            + signals.amber_time
+            + signals.all_red_time
+            + signals.red_amber_time) as f64
+    }
+
+    /// The crosswalk across `leg`, the arm of the road lying in that
+    /// direction from the box.
+    pub fn crosswalk(&self, leg: Direction) -> Crosswalk {
+        let (cx, cy) = self.center();
+        let near =
+            leg.along(cx, cy) + self.carriageway(leg.left()) + self.queue_depth() + CROSSWALK_GAP;
+        let middle = leg.lateral(cx, cy);
+        Crosswalk {
+            leg,
+            near,
+            far: near + self.pedestrians.width,
+            start: middle - self.carriageway(leg.opposite()),
+            end: middle + self.carriageway(leg),
+        }
+    }
+
+    /// The lane a movement from approach lane `lane` leaves on. Through
+    /// traffic keeps its lane; turning lanes fill the exit from the side
+    /// nearest the turn, so the innermost left lane takes the innermost
+    /// exit lane and the outermost right lane the outermost one. Cyclists
+    /// keep to the bike lanes.
+    pub fn exit_lane(&self, direction: Direction, lane: usize, turn: Turn) -> usize {
+        if lane >= self.bike_lane(direction) {
+            return self.bike_lane(direction.turned(turn));
+        }
+        let lanes = self.lanes(direction);
+        let exits = self.lanes(direction.turned(turn)).len();
+        match turn {
+            Turn::Front => lane.min(exits - 1),
+            Turn::Left => {
+                let inside = lanes[..lane].iter().filter(|l| l.allows(turn)).count();
+                inside.min(exits - 1)
+            }
+            Turn::Right => {
+                let outside = lanes[lane + 1..].iter().filter(|l| l.allows(turn)).count();
+                exits.saturating_sub(outside + 1)
+            }
+        }
+    }
+
+    /// Where the centre line of the approach lane meets that of the exit
+    /// lane; the middle of the box on the lane for through movements.
+    pub fn crossing(&self, direction: Direction, lane: usize, turn: Turn) -> (f32, f32) {
+        let (ax, ay) = self.lane_point(direction, lane);
+        let (ex, ey) = match turn {
+            Turn::Front => self.center(),
+            _ => self.lane_point(
+                direction.turned(turn),
+                self.exit_lane(direction, lane, turn),
+            ),
+        };
+        match direction {
+            Direction::North | Direction::South => (ax, ey),
+            Direction::East | Direction::West => (ex, ay),
+        }
+    }
+
+    /// Centre position along the approach of the lane crossing for a turn.
+    pub fn turn_point(&self, direction: Direction, lane: usize, turn: Turn) -> f32 {
+        let (x, y) = self.crossing(direction, lane, turn);
+        direction.along(x, y)
+    }
+
+    /// Radius of the arc a turn follows from `lane`. Rights sweep round the
+    /// corner of the box; lefts turn tightly near the far lane so that
+    /// opposing left turners can wait and go side by side. Cyclists turn
+    /// either way within their bike lane.
+    pub fn turn_radius(&self, direction: Direction, lane: usize, turn: Turn) -> f32 {
+        match turn {
+            Turn::Front => 0.0,
+            _ if lane >= self.bike_lane(direction) => self.bike_lane_width() / 2.0,
+            Turn::Left => self.lane_width / 4.0,
+            Turn::Right => self.lane_width / 2.0,
+        }
+    }
+
+    /// The heading a turn from `lane` leaves the box in. A cyclist's left is
+    /// made in two stages, the first of them a right into the queue box
+    /// across the street it turns into.
+    pub fn exit_heading(&self, direction: Direction, lane: usize, turn: Turn) -> Direction {
+        if turn == Turn::Left && lane >= self.bike_lane(direction) {
+            direction.right()
+        } else {
+            direction.turned(turn)
+        }
+    }
+
+    /// Centre position along the approach at which a car leaves its
+    /// approach lane and starts to turn.
+    pub fn turn_start(&self, direction: Direction, lane: usize, turn: Turn) -> f32 {
+        self.turn_point(direction, lane, turn) - self.turn_radius(direction, lane, turn)
+    }
+
+    pub fn turn_arc(&self, direction: Direction, lane: usize, turn: Turn) -> Option<TurnArc> {
+        if turn == Turn::Front {
+            return None;
+        }
+        let (x, y) = self.crossing(direction, lane, turn);
+        let exit = self.exit_heading(direction, lane, turn);
+        let radius = self.turn_radius(direction, lane, turn);
+        Some(TurnArc::new(direction, exit, x, y, radius))
+    }
+
+    /// Where a vehicle of `class` heading in `direction` enters `lane`: just
+    /// outside the window, front on the edge.
+    pub fn spawn_point(
+        &self,
+        class: VehicleClass,
+        direction: Direction,
+        lane: usize,
+    ) -> (f32, f32) {
+        let (lane_x, lane_y) = self.lane_point(direction, lane);
+        let half = self.spec(class).length / 2.0;
+        match direction {
+            Direction::North => (lane_x, self.window.height + half),
+            Direction::South => (lane_x, -half),
+            Direction::East => (-half, lane_y),
+            Direction::West => (self.window.width + half, lane_y),
+        }
+    }
+
+    /// Top-left corner of the signal head for cars travelling in `direction`,
+    /// by default on the kerb to the right of the stop line.
+    pub fn light_position(&self, direction: Direction) -> (f32, f32) {
+        if let Some([x, y]) = self.approach.get(direction).light {
+            return (x, y);
+        }
+        let (cx, cy) = self.center();
+        let edge = self.carriageway(direction.right()) + self.setback(direction);
+        let side = self.carriageway(direction);
+        match direction {
+            Direction::North => (cx + side, cy + edge),
+            Direction::South => (cx - side - LIGHT_WIDTH, cy - edge - LIGHT_HEIGHT),
+            Direction::East => (cx - edge - LIGHT_WIDTH, cy + side),
+            Direction::West => (cx + edge, cy - side - LIGHT_HEIGHT),
+        }
+    }
+
+    /// Centre of the stop sign for cars travelling in `direction` at an
+    /// all-way stop, where their signal head would otherwise stand.
+    pub fn stop_sign_position(&self, direction: Direction) -> (f32, f32) {
+        let (x, y) = self.light_position(direction);
+        (x + LIGHT_WIDTH / 2.0, y + LIGHT_HEIGHT / 2.0)
+    }
+
+    pub fn contains(&self, x: f32, y: f32, margin: f32) -> bool {
+        x > -margin
+            && x < self.window.width + margin
+            && y > -margin
+            && y < self.window.height + margin
+    }
+}
+
+// Most lanes an approach may have; the box's cells have to fit in `Cells`.
+const MAX_LANES: usize = 4;
+
+// Space between a crosswalk and the box, and between it and the stop line.
+const CROSSWALK_GAP: f32 = 4.0;
+
+// Least space in front of and behind a cyclist waiting in a queue box, enough
+// for someone crossing diagonally past a square corner to clear it.
+const QUEUE_BOX_GAP: f32 = 2.0 * pedestrian::RADIUS;
+
+// Footprint of a drawn signal head.
+const LIGHT_WIDTH: f32 = 48.0;
+const LIGHT_HEIGHT: f32 = 50.0;
+
+pub fn direction_key(direction: Direction) -> &'static str {
+    match direction {
+        Direction::North => "north",
+        Direction::South => "south",
+        Direction::East => "east",
+        Direction::West => "west",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // What is wrong with a scenario file reading `text`.
+    fn error(text: &str) -> String {
+        match toml::from_str::<Scenario>(text) {
+            Ok(scenario) => scenario.validate().unwrap_err().to_string(),
+            Err(err) => ScenarioError::Parse(err.to_string()).to_string(),
+        }
+    }
+
+    #[test]
+    fn negative_size_names_the_field() {
+        let message = error("lane_width = -50.0");
+        assert!(message.contains("`lane_width`"), "{message}");
+    }
+
+    #[test]
+    fn car_wider_than_its_lane_names_the_field() {
+        let message = error("[car]\nwidth = 60.0");
+        assert!(message.contains("`car.width`"), "{message}");
+    }
+
+    #[test]
+    fn stop_line_past_the_window_edge_names_the_approach() {
+        let message = error("[approach.south]\nstop_line_offset = 1000.0");
+        assert!(
+            message.contains("`approach.south.stop_line_offset`"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn light_outside_the_window_names_the_approach() {
+        let message = error("[approach.east]\nlight = [-10.0, 20.0]");
+        assert!(message.contains("`approach.east.light`"), "{message}");
+    }
+
+    #[test]
+    fn negative_timing_names_the_field() {
+        let message = error("[signals]\namber_time = -1.0");
+        assert!(message.contains("`signals.amber_time`"), "{message}");
+    }
+
+    #[test]
+    fn max_green_shorter_than_min_green_names_the_approach() {
+        let message = error("[approach.west]\nmin_green = 5.0\nmax_green = 3.0");
+        assert!(message.contains("`approach.west.max_green`"), "{message}");
+    }
+
+    #[test]
+    fn detector_beyond_the_road_names_the_detector() {
+        let text = r#"
+[[detectors]]
+approach = "north"
+kind = "passage"
+distance = 5000.0
+length = 20.0
+"#;
+        let message = error(text);
+        assert!(message.contains("`detectors[0]`"), "{message}");
+    }
+
+    #[test]
+    fn class_wider_than_its_lane_names_the_class() {
+        let message = error("[vehicles.truck]\nwidth = 70.0");
+        assert!(message.contains("`vehicles.truck.width`"), "{message}");
+    }
+
+    #[test]
+    fn unknown_lane_use_names_the_lanes() {
+        let message = error("[approach.north]\nlanes = [\"left\", \"sideways\"]");
+        assert!(
+            message.contains("lanes") && message.contains("sideways"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn approach_without_a_lane_for_a_turn_names_the_lanes() {
+        let message = error("[approach.north]\nlanes = [\"left\", \"through\"]");
+        assert!(message.contains("`approach.north.lanes`"), "{message}");
+    }
+}
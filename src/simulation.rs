@@ -0,0 +1,2011 @@
+use crate::app::*;
+use crate::conflict::{ConflictZone, overlaps};
+use crate::controller::{ControllerView, GreenGuard, GuardStats, Phase, SignalController};
+use crate::detector::{Detector, DetectorKind};
+use crate::pedestrian::{Pedestrian, Route, WalkSignal, WalkState};
+use crate::scenario::{Control, Scenario, VehicleSpec};
+use crate::stats::Statistics;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::fmt;
+
+// Fixed simulation step, independent of the render frame rate.
+pub const TICK: f32 = 1.0 / 64.0;
+
+// How close to the stop line a standing car counts as stopped at it.
+const STOPPED_AT_LINE: f32 = 0.5;
+
+// Cars aim this far past each point they must stop at, so the driver model
+// pulls them right up to it instead of approaching it ever more slowly.
+const CREEP: f32 = 2.0;
+
+// Vehicles stopping at an all-way stop within this many seconds of each
+// other count as having arrived together.
+const ARRIVAL_TIE: f64 = 0.5;
+
+const COLORS: [(Col, Turn); 3] = [
+    (Col::Darkblue, Turn::Left),
+    (Col::Pink, Turn::Right),
+    (Col::Gold, Turn::Front),
+];
+
+/// The traffic model without any rendering: owns the cars, the pedestrians,
+/// the lights and the per-direction counters, and advances them with `step`.
+///
+/// Every random decision goes through a single RNG seeded at construction, so
+/// the same seed and the same sequence of calls reproduce a run exactly.
+pub struct Simulation {
+    pub scenario: Scenario,
+    pub cars: Vec<Car>,
+    pub pedestrians: Vec<Pedestrian>,
+    pub lights: Vec<TrafficLight>,
+    // Walk signals by the leg their crosswalk is on, and the one for
+    // crossing diagonally in a scramble.
+    pub walk_signals: [WalkSignal; 4],
+    pub diagonal_signal: WalkSignal,
+    pub detectors: Vec<Detector>,
+    pub counts: [u32; 4],
+    pub time: f64,
+    pub seed: u64,
+    pub phase: Phase,
+    pub stats: Statistics,
+    pub zone: ConflictZone,
+    // Problems found while stepping, e.g. cars that ran into each other.
+    pub errors: Vec<SimulationError>,
+    rng: ChaCha8Rng,
+    next_id: u32,
+    next_pedestrian: u32,
+    // Pairs of cars overlapping as of the last step, so each collision is
+    // reported once; likewise (car, pedestrian) pairs.
+    colliding: Vec<[u32; 2]>,
+    striking: Vec<[u32; 2]>,
+    controller: GreenGuard,
+    // When the current phase turned green, and when it was last confirmed.
+    phase_start: f64,
+    last_change: f64,
+    // Change of phase in progress.
+    pending: Option<Transition>,
+    flashing: bool,
+    // The emergency vehicle the signals are currently preempted for.
+    preempting: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimulationError {
+    // Two cars' footprints overlap; `x`, `y` is midway between them.
+    Collision {
+        time: f64,
+        cars: [u32; 2],
+        x: f32,
+        y: f32,
+    },
+    // A vehicle ran into someone; `x`, `y` is where they stood.
+    PedestrianStruck {
+        time: f64,
+        car: u32,
+        pedestrian: u32,
+        x: f32,
+        y: f32,
+    },
+}
+
+impl fmt::Display for SimulationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimulationError::Collision { time, cars, x, y } => write!(
+                f,
+                "{time:.2}s: cars {} and {} collided at ({x:.0}, {y:.0})",
+                cars[0], cars[1]
+            ),
+            SimulationError::PedestrianStruck {
+                time,
+                car,
+                pedestrian,
+                x,
+                y,
+            } => write!(
+                f,
+                "{time:.2}s: car {car} struck pedestrian {pedestrian} at ({x:.0}, {y:.0})"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SimulationError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Clearance {
+    // Pedestrians who may no longer walk get their flashing don't walk,
+    // with the greens held until it is over.
+    Pedestrians,
+    Amber,
+    AllRed,
+    RedAmber,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Transition {
+    next: Phase,
+    stage: Clearance,
+    since: f64,
+    // Whether any green had to be ended; if not, amber and all-red are skipped.
+    terminating: bool,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self::new(Scenario::default(), rand::random())
+    }
+}
+
+impl Simulation {
+    pub fn new(scenario: Scenario, seed: u64) -> Self {
+        // Each light stands on the side its cars arrive from. An all-way
+        // stop has none.
+        let lights = match scenario.control {
+            Control::Signals => Direction::ALL
+                .iter()
+                .map(|&direction| {
+                    let (x, y) = scenario.light_position(direction);
+                    TrafficLight::new(direction.opposite(), x, y, LightState::Red)
+                })
+                .collect(),
+            Control::AllWayStop => Vec::new(),
+        };
+
+        let detectors = scenario
+            .detectors
+            .iter()
+            .copied()
+            .map(Detector::new)
+            .collect();
+        let zone = ConflictZone::new(&scenario);
+
+        Self {
+            controller: GreenGuard::new(
+                scenario.signals.controller.build(&scenario),
+                Direction::ALL.map(|d| scenario.approach.get(d).limits()),
+                scenario.pedestrians.max_wait as f64 - scenario.walk_lead(),
+                scenario.pedestrians.scramble,
+                scenario.phases(),
+            ),
+            scenario,
+            cars: Vec::new(),
+            pedestrians: Vec::new(),
+            lights,
+            walk_signals: [WalkSignal::default(); 4],
+            diagonal_signal: WalkSignal::default(),
+            detectors,
+            counts: [0; 4],
+            time: 0.0,
+            seed,
+            phase: Phase::ALL_RED,
+            stats: Statistics::default(),
+            zone,
+            errors: Vec::new(),
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            next_id: 0,
+            next_pedestrian: 0,
+            colliding: Vec::new(),
+            striking: Vec::new(),
+            phase_start: 0.0,
+            last_change: 0.0,
+            pending: None,
+            flashing: false,
+            preempting: None,
+        }
+    }
+
+    /// Replaces the signal controller, e.g. with a custom strategy. The
+    /// scenario's green-time limits still apply on top of it.
+    pub fn set_controller(&mut self, controller: Box<dyn SignalController>) {
+        self.controller.set_inner(controller);
+    }
+
+    /// How often the green-time limits overrode the controller.
+    pub fn guard_stats(&self) -> &GuardStats {
+        &self.controller.stats
+    }
+
+    pub fn controller_name(&self) -> &'static str {
+        match self.scenario.control {
+            Control::Signals => self.controller.name(),
+            Control::AllWayStop => Control::AllWayStop.name(),
+        }
+    }
+
+    pub fn spawn(&mut self, direction: Direction) -> bool {
+        let (col, turn) = COLORS[self.rng.random_range(0..COLORS.len())];
+        let class = self.pick_class();
+        self.spawn_vehicle(direction, class, col, turn)
+    }
+
+    pub fn spawn_emergency(&mut self, direction: Direction) -> bool {
+        let (col, turn) = COLORS[self.rng.random_range(0..COLORS.len())];
+        self.spawn_vehicle(direction, VehicleClass::Emergency, col, turn)
+    }
+
+    /// Puts a cyclist in the bike lane travelling in `direction`. Does
+    /// nothing unless the scenario has bike lanes.
+    pub fn spawn_bicycle(&mut self, direction: Direction) -> bool {
+        if !self.scenario.bicycles.enabled {
+            return false;
+        }
+        let (col, turn) = COLORS[self.rng.random_range(0..COLORS.len())];
+        self.spawn_vehicle(direction, VehicleClass::Bicycle, col, turn)
+    }
+
+    fn spawn_vehicle(
+        &mut self,
+        direction: Direction,
+        class: VehicleClass,
+        col: Col,
+        turn: Turn,
+    ) -> bool {
+        let spec = self.scenario.spec(class).clone();
+
+        let lane = if class == VehicleClass::Bicycle {
+            self.scenario.bike_lane(direction)
+        } else {
+            self.pick_lane(direction, turn)
+        };
+        let start = self.scenario.spawn_point(class, direction, lane);
+        if !self.can_spawn(direction, start, &spec) {
+            return false;
+        }
+
+        self.counts[direction.index()] += 1;
+        self.stats.spawned[direction.index()] += 1;
+        self.stats.spawned_by_class[class.index()] += 1;
+        let mut car = Car::new(self.next_id, class, direction, start, col, turn, &spec);
+        car.lane = lane;
+        car.path = ConflictZone::path(&self.scenario, &car);
+        car.spawned_at = self.time;
+        self.cars.push(car);
+        self.next_id += 1;
+        true
+    }
+
+    // One of the approach's lanes marked for `turn`, at random.
+    fn pick_lane(&mut self, direction: Direction, turn: Turn) -> usize {
+        let lanes: Vec<usize> = (self.scenario.lanes(direction).iter().enumerate())
+            .filter(|(_, lane)| lane.allows(turn))
+            .map(|(i, _)| i)
+            .collect();
+        lanes[self.rng.random_range(0..lanes.len())]
+    }
+
+    fn pick_class(&mut self) -> VehicleClass {
+        let mix = &self.scenario.vehicles.mix;
+        let total: f32 = VehicleClass::ALL.map(|class| mix.share(class)).iter().sum();
+        let mut pick = self.rng.random_range(0.0..total);
+        for class in VehicleClass::ALL {
+            pick -= mix.share(class);
+            if pick < 0.0 {
+                return class;
+            }
+        }
+        VehicleClass::Car
+    }
+
+    pub fn spawn_random(&mut self) -> bool {
+        let direction = Direction::ALL[self.rng.random_range(0..Direction::ALL.len())];
+        self.spawn(direction)
+    }
+
+    pub fn spawn_emergency_random(&mut self) -> bool {
+        let direction = Direction::ALL[self.rng.random_range(0..Direction::ALL.len())];
+        self.spawn_emergency(direction)
+    }
+
+    pub fn spawn_bicycle_random(&mut self) -> bool {
+        let direction = Direction::ALL[self.rng.random_range(0..Direction::ALL.len())];
+        self.spawn_bicycle(direction)
+    }
+
+    /// Puts someone on one of the corners beside `leg`'s crosswalk, to cross
+    /// to the other. Does nothing unless the scenario has crosswalks.
+    pub fn spawn_pedestrian(&mut self, leg: Direction) -> bool {
+        if !self.scenario.pedestrians.enabled {
+            return false;
+        }
+        let heading = if self.rng.random_bool(0.5) {
+            leg.right()
+        } else {
+            leg.left()
+        };
+        let lane = self.rng.random_range(0.0..=1.0);
+        let crosswalk = self.scenario.crosswalk(leg);
+        let speed = self.scenario.pedestrians.speed;
+        let pedestrian = Pedestrian::across(
+            self.next_pedestrian,
+            &crosswalk,
+            heading,
+            lane,
+            speed,
+            self.time,
+        );
+        self.pedestrians.push(pedestrian);
+        self.next_pedestrian += 1;
+        self.stats.pedestrians_spawned[leg.index()] += 1;
+        true
+    }
+
+    /// Puts someone on the corner between the legs `corner` and
+    /// `corner.right()`, to cross diagonally to the opposite one in a
+    /// scramble. Does nothing unless the scenario runs scrambles.
+    pub fn spawn_diagonal(&mut self, corner: Direction) -> bool {
+        if !self.scenario.pedestrians.scramble.is_enabled() {
+            return false;
+        }
+        let pedestrian = Pedestrian::new(
+            self.next_pedestrian,
+            Route::Diagonal,
+            self.scenario.waiting_spot(corner),
+            self.scenario.waiting_spot(corner.opposite()),
+            self.scenario.pedestrians.speed,
+            self.time,
+        );
+        self.pedestrians.push(pedestrian);
+        self.next_pedestrian += 1;
+        self.stats.diagonals_spawned += 1;
+        true
+    }
+
+    /// A pedestrian on a random corner, crossing either leg beside it or,
+    /// with scrambles, diagonally.
+    pub fn spawn_pedestrian_random(&mut self) -> bool {
+        let corner = Direction::ALL[self.rng.random_range(0..Direction::ALL.len())];
+        if self.scenario.pedestrians.scramble.is_enabled() && self.rng.random_ratio(1, 3) {
+            return self.spawn_diagonal(corner);
+        }
+        let leg = Direction::ALL[self.rng.random_range(0..Direction::ALL.len())];
+        self.spawn_pedestrian(leg)
+    }
+
+    pub fn step(&mut self, dt: f32) {
+        self.time += dt as f64;
+
+        let margin = self.scenario.lane_width * 2.0 + self.scenario.longest_vehicle();
+        let scenario = &self.scenario;
+        self.cars
+            .retain(|car| scenario.contains(car.x, car.y, margin));
+
+        match self.scenario.control {
+            Control::Signals => self.update_lights(),
+            Control::AllWayStop => self.update_stop_signs(),
+        }
+        self.update_walk_signals();
+        self.move_pedestrians(dt);
+        self.move_cars(dt);
+        self.record_preemption(dt);
+        self.change_lanes();
+        self.turn_cars();
+        self.zone.update(&self.cars, &self.scenario);
+        self.detect_collisions();
+        self.detect_strikes();
+
+        for detector in &mut self.detectors {
+            detector.update(&self.cars, &self.scenario, self.time);
+        }
+    }
+
+    /// Puts every signal into flashing amber and suspends the controller, or
+    /// returns to normal operation starting from all red. An all-way stop
+    /// has no signals to flash.
+    pub fn set_flashing(&mut self, on: bool) {
+        if self.flashing == on || self.scenario.control == Control::AllWayStop {
+            return;
+        }
+        self.flashing = on;
+        self.pending = None;
+        self.phase = Phase::ALL_RED;
+        self.last_change = self.time;
+        let state = if on {
+            LightState::Flashing
+        } else {
+            LightState::Red
+        };
+        for light in &mut self.lights {
+            light.set_all(state, self.time);
+        }
+    }
+
+    pub fn is_flashing(&self) -> bool {
+        self.flashing
+    }
+
+    fn update_lights(&mut self) {
+        if self.flashing {
+            return;
+        }
+
+        // An emergency vehicle takes the signals over until it has gone
+        // through; the controller then carries on from there.
+        if let Some(vehicle) = self.emergency() {
+            let (id, target) = (vehicle.id, Phase::approach(vehicle.direction));
+            if self.preempting != Some(id) {
+                self.preempting = Some(id);
+                self.stats.preemptions += 1;
+            }
+            match self.pending {
+                Some(transition) if transition.next != target => self.begin_transition(target),
+                Some(_) => self.advance_transition(),
+                None if self.phase != target => self.begin_transition(target),
+                None => {}
+            }
+            return;
+        }
+        self.preempting = None;
+
+        if self.pending.is_some() {
+            self.advance_transition();
+            return;
+        }
+
+        let now = self.time;
+        if now - self.last_change < self.scenario.signals.green_time as f64 {
+            return;
+        }
+
+        let view = self.controller_view();
+        let next = self.controller.next_phase(&view, &mut self.rng);
+        self.last_change = now;
+        if next != self.phase {
+            self.begin_transition(next);
+        }
+    }
+
+    // Lets the walks not carried into `next` run out and clear, then ends
+    // the arrows not carried unchanged with amber, then all red. A change
+    // already under way is cut short and cleared again in full.
+    fn begin_transition(&mut self, next: Phase) {
+        let now = self.time;
+        for light in &mut self.lights {
+            for turn in Turn::ALL {
+                if light.state(turn) == LightState::RedAmber {
+                    light.set(turn, LightState::Red, now);
+                }
+            }
+        }
+        self.pending = Some(Transition {
+            next,
+            stage: Clearance::Pedestrians,
+            since: now,
+            terminating: self.pending.is_some(),
+        });
+        self.advance_transition();
+    }
+
+    // Puts the arrows not carried unchanged into `next` on amber; whether
+    // any had been open.
+    fn end_greens(&mut self, next: Phase) -> bool {
+        let now = self.time;
+        let mut terminating = false;
+        for (light, direction) in self.lights.iter_mut().zip(Direction::ALL) {
+            for turn in Turn::ALL {
+                let state = light.state(turn);
+                let open = matches!(state, LightState::Green | LightState::Flashing);
+                if open && state != next.signal(direction, turn) {
+                    light.set(turn, LightState::Amber, now);
+                    terminating = true;
+                }
+            }
+        }
+        terminating
+    }
+
+    fn advance_transition(&mut self) {
+        let now = self.time;
+        let signals = &self.scenario.signals;
+        let (amber_time, all_red_time, red_amber_time) = (
+            signals.amber_time,
+            signals.all_red_time,
+            signals.red_amber_time,
+        );
+
+        while let Some(mut transition) = self.pending {
+            let duration = match transition.stage {
+                Clearance::Amber if transition.terminating => amber_time,
+                Clearance::AllRed if transition.terminating => all_red_time,
+                Clearance::RedAmber => red_amber_time,
+                _ => 0.0,
+            };
+            if now - transition.since < duration as f64 {
+                return;
+            }
+
+            match transition.stage {
+                Clearance::Pedestrians => {
+                    let next = transition.next;
+                    let clearing =
+                        self.walk_signals
+                            .iter()
+                            .zip(Direction::ALL)
+                            .any(|(signal, leg)| {
+                                signal.state != WalkState::DontWalk && !next.walks(leg)
+                            })
+                            || self.diagonal_signal.state != WalkState::DontWalk
+                                && !next.walks_diagonally();
+                    if clearing {
+                        return;
+                    }
+                    transition.terminating |= self.end_greens(transition.next);
+                    transition.stage = Clearance::Amber;
+                }
+                Clearance::Amber => {
+                    for light in &mut self.lights {
+                        for turn in Turn::ALL {
+                            if light.state(turn) == LightState::Amber {
+                                light.set(turn, LightState::Red, now);
+                            }
+                        }
+                    }
+                    self.phase = Phase::ALL_RED;
+                    transition.stage = Clearance::AllRed;
+                }
+                Clearance::AllRed => {
+                    // A scramble needs the box empty; turners still in it
+                    // may be letting someone over a crosswalk first.
+                    if transition.next.walks_diagonally() && !self.zone.is_empty() {
+                        return;
+                    }
+                    if red_amber_time > 0.0 {
+                        for (light, direction) in self.lights.iter_mut().zip(Direction::ALL) {
+                            for turn in Turn::ALL {
+                                let target = transition.next.signal(direction, turn);
+                                if target == LightState::Green && !light.is_green(turn) {
+                                    light.set(turn, LightState::RedAmber, now);
+                                }
+                            }
+                        }
+                    }
+                    transition.stage = Clearance::RedAmber;
+                }
+                Clearance::RedAmber => {
+                    for (light, direction) in self.lights.iter_mut().zip(Direction::ALL) {
+                        for turn in Turn::ALL {
+                            light.set(turn, transition.next.signal(direction, turn), now);
+                        }
+                    }
+                    self.phase = transition.next;
+                    self.phase_start = now;
+                    self.last_change = now;
+                    self.pending = None;
+                    return;
+                }
+            }
+            transition.since = now;
+            self.pending = Some(transition);
+        }
+    }
+
+    pub fn controller_view(&self) -> ControllerView {
+        let mut stopped = [0; 4];
+        let mut queues = [[0; 3]; 4];
+        let mut downstream = [0; 4];
+        for car in &self.cars {
+            // A cyclist in a queue box waits on the through movement it
+            // faces.
+            if car.queued {
+                queues[car.heading.index()][Turn::Front.index()] += 1;
+                if !car.check_move {
+                    stopped[car.heading.index()] += 1;
+                }
+                continue;
+            }
+            if car.check {
+                downstream[car.heading.index()] += 1;
+                continue;
+            }
+            queues[car.direction.index()][car.signal_turn().index()] += 1;
+            if !car.check_move && car.heading == car.direction {
+                stopped[car.direction.index()] += 1;
+            }
+        }
+        let mut presence = [false; 4];
+        let mut last_passage: [Option<f64>; 4] = [None; 4];
+        for detector in &self.detectors {
+            let i = detector.spec.approach.index();
+            match detector.spec.kind {
+                DetectorKind::Presence => presence[i] |= detector.occupied,
+                DetectorKind::Passage => {
+                    if let Some(t) = detector.last_passage {
+                        last_passage[i] = Some(last_passage[i].map_or(t, |prev: f64| prev.max(t)));
+                    }
+                }
+            }
+        }
+
+        let mut pedestrian_wait = [0.0; 4];
+        let mut diagonal_wait = 0.0;
+        for pedestrian in self.pedestrians.iter().filter(|p| !p.is_crossing()) {
+            let wait = match pedestrian.route {
+                Route::Leg(leg) => &mut pedestrian_wait[leg.index()],
+                Route::Diagonal => &mut diagonal_wait,
+            };
+            *wait = f64::max(*wait, self.time - pedestrian.spawned_at);
+        }
+
+        ControllerView {
+            time: self.time,
+            counts: self.counts,
+            stopped,
+            queues,
+            downstream,
+            presence,
+            last_passage,
+            pedestrian_wait,
+            diagonal_wait,
+            current: self.phase,
+            elapsed: self.time - self.phase_start,
+        }
+    }
+
+    fn move_cars(&mut self, dt: f32) {
+        let turns = match self.scenario.control {
+            Control::Signals => Vec::new(),
+            Control::AllWayStop => self.stop_sign_turns(),
+        };
+        for i in 0..self.cars.len() {
+            let car = &self.cars[i];
+            let along = car.along();
+            let lateral = car.lateral();
+            // Points ahead the car has to stop at, as the room left before
+            // each and how fast the car is closing on it.
+            let mut stops = Vec::new();
+            let mut turning_on_red = false;
+            let mut to_box = None;
+
+            // Stop at the line for the light or the stop sign, or for a box
+            // that can't be entered yet.
+            if !car.check && car.heading == car.direction {
+                let front = along + car.length / 2.0;
+                let to_stop_line = self.stop_line(car) - front;
+                let can_stop = !car.check_move || to_stop_line >= car.stopping_distance();
+                let stop_sign = self.scenario.control == Control::AllWayStop;
+                let must_stop = match self.signal(car.direction, car.signal_turn()) {
+                    // At a stop sign everyone but an emergency vehicle
+                    // waits for their turn.
+                    _ if stop_sign && car.class != VehicleClass::Emergency => {
+                        !turns.contains(&car.id) || self.yields(car) && can_stop
+                    }
+                    // Everyone else holds back for an emergency vehicle
+                    // where they still can.
+                    LightState::Green | LightState::Flashing => self.yields(car) && can_stop,
+                    LightState::Red => {
+                        turning_on_red =
+                            !self.yields(car) && self.may_turn_on_red(car, to_stop_line);
+                        !turning_on_red
+                    }
+                    LightState::RedAmber => true,
+                    // Stop on amber only if the car is already standing or
+                    // can still brake comfortably before the line.
+                    LightState::Amber => can_stop,
+                };
+                if to_stop_line >= 0.0 {
+                    // Entering the box takes a reservation on the cells
+                    // ahead; a left turner that may have to wait for a gap
+                    // only takes those up to its turn point.
+                    let (approach, exit) = car.path;
+                    let cells = if self.waits_for_gap(car) {
+                        approach
+                    } else {
+                        approach | exit
+                    };
+                    to_box = Some((to_stop_line, cells));
+                    if must_stop
+                        || !self.may_enter(car)
+                        || self.gives_way_on_foot(car)
+                        || self.cuts_across_cyclist(car)
+                        || !self.queue_box_clear(car)
+                    {
+                        stops.push((to_stop_line, car.speed));
+                    }
+                }
+
+                // A permissive left turner pulls up to where its turn starts and
+                // waits there for a gap in the oncoming traffic.
+                let front = along + car.length / 2.0;
+                let to_turn = self.scenario.turn_start(car.direction, car.lane, car.turn) - front;
+                if to_turn >= 0.0 && self.waits_to_turn(car) {
+                    stops.push((to_turn, car.speed));
+                }
+            }
+
+            // Turning or going on over a crosswalk someone is still crossing,
+            // it pulls up just short of it.
+            if let Some(room) = self.exit_crosswalk_room(car) {
+                stops.push((room, car.speed));
+            }
+
+            // A two-stage left rides on into the queue box after its first
+            // stage, and from there across with the traffic it then faces.
+            if car.two_stage() && car.check && !car.queued && car.heading == car.direction.right() {
+                let (x, y) = self.scenario.queue_box(car.direction);
+                stops.push((car.heading.along(x, y) - along, car.speed));
+            }
+            if car.queued {
+                // It takes its cells as it pulls away and from then on rides
+                // like anyone else in the box; till then it waits where it
+                // is, clear of the corner.
+                let (approach, exit) = car.path;
+                to_box = Some((0.0, approach | exit));
+                let go = matches!(
+                    self.signal(car.heading, Turn::Front),
+                    LightState::Green | LightState::Flashing
+                ) && !self.yields(car);
+                if !go || !self.may_enter(car) || self.gives_way_on_foot(car) {
+                    stops.push((0.0, car.speed));
+                }
+            }
+
+            for (j, front_car) in self.cars.iter().enumerate() {
+                if i == j {
+                    continue;
+                }
+                let (rear_along, speed, gap) = if front_car.heading == car.heading {
+                    if !self.shares_lane(car, front_car) {
+                        continue;
+                    }
+                    let rear_along = front_car.along() - front_car.length / 2.0;
+                    (rear_along, front_car.speed, self.scenario.safe_gap)
+                } else if front_car.arc.is_some() && front_car.direction == car.heading {
+                    // Still swinging off this lane, with its tail in it.
+                    let (x, y) = front_car.rear();
+                    if (car.heading.lateral(x, y) - lateral).abs() >= self.scenario.lane_width / 2.0
+                    {
+                        continue;
+                    }
+                    (
+                        car.heading.along(x, y),
+                        front_car.speed,
+                        self.scenario.safe_gap,
+                    )
+                } else if front_car.heading == car.heading.opposite()
+                    && car.class == VehicleClass::Bicycle
+                    && front_car.class == VehicleClass::Bicycle
+                {
+                    // Head on in the same bike lane, one of them riding into
+                    // a queue box against its flow. The box ends where the
+                    // other stops for its line, so they may close right up.
+                    let across = car.heading.lateral(front_car.x, front_car.y);
+                    if (across - lateral).abs() >= car.width {
+                        continue;
+                    }
+                    let near_end = car.heading.along(front_car.x, front_car.y);
+                    (near_end - front_car.length / 2.0, -front_car.speed, 0.0)
+                } else {
+                    continue;
+                };
+                if car.heading.along(front_car.x, front_car.y) > along {
+                    let room = rear_along - (along + car.length / 2.0) - gap;
+                    stops.push((room, car.speed - speed));
+                }
+            }
+
+            let mut accel = car.acceleration(None);
+            let mut limit = f32::INFINITY;
+            for (room, closing) in stops {
+                accel = accel.min(car.acceleration(Some((room + CREEP, closing))));
+                limit = limit.min(room);
+            }
+            let speed = (car.speed + accel * dt).clamp(0.0, car.max_speed);
+            let mut advance = (speed * dt).min(limit);
+
+            // Cars ahead in this step may have taken the cells meanwhile.
+            if let Some((to_stop_line, cells)) = to_box
+                && advance > to_stop_line
+            {
+                if self.may_enter(car) {
+                    self.zone.reserve(cells, car.id);
+                } else {
+                    advance = to_stop_line;
+                }
+            }
+
+            if turning_on_red && advance > 0.0 {
+                self.stats.right_turns_on_red[car.direction.index()] += 1;
+            }
+            let leaves_queue = car.queued && advance > 0.0;
+            let advance = advance.max(0.0);
+            let car = &mut self.cars[i];
+            if leaves_queue {
+                car.queued = false;
+            }
+            // Pulled up short by a hard stop: it ends the step at that speed.
+            car.speed = if advance < speed * dt {
+                advance / dt
+            } else {
+                speed
+            };
+            car.advance(advance);
+        }
+    }
+
+    // Runs each crosswalk's signal: walk while the phase gives one and
+    // someone is waiting, then flashing don't walk for as long as crossing
+    // takes. In a scramble the crosswalks all walk with the diagonals.
+    fn update_walk_signals(&mut self) {
+        let now = self.time;
+        // The crosswalks at an all-way stop have no signals: pedestrians
+        // step out whenever it is their turn.
+        if self.scenario.control == Control::AllWayStop {
+            for signal in &mut self.walk_signals {
+                signal.set(WalkState::Walk, now);
+            }
+            return;
+        }
+        let settings = &self.scenario.pedestrians;
+        let walk_time = settings.walk_time as f64;
+        let may_walk = !self.flashing && self.pending.is_none() && self.preempting.is_none();
+        let waiting = |route: Route| {
+            self.pedestrians
+                .iter()
+                .any(|p| p.route == route && !p.is_crossing())
+        };
+        let crossing = |route: Route| {
+            self.pedestrians
+                .iter()
+                .any(|p| p.route == route && p.is_crossing())
+        };
+        let diagonal = may_walk && self.phase.walks_diagonally() && waiting(Route::Diagonal);
+        let calls = Direction::ALL
+            .map(|leg| may_walk && self.phase.walks(leg) && (diagonal || waiting(Route::Leg(leg))));
+        // A walk nobody has been able to use yet, with whatever came up to
+        // it still driving over the crosswalk, only starts counting down
+        // once that has gone.
+        let held = Direction::ALL.map(|leg| {
+            let since = self.walk_signals[leg.index()].since;
+            let used = self.pedestrians.iter().any(|p| {
+                p.route == Route::Leg(leg) && p.started_at.is_some_and(|start| start >= since)
+            });
+            !used
+                && waiting(Route::Leg(leg))
+                && self.cars.iter().any(|car| self.drives_over(car, leg))
+        });
+        for leg in Direction::ALL {
+            if held[leg.index()] {
+                self.walk_signals[leg.index()].hold(now);
+            }
+            let clearance = self.scenario.crosswalk(leg).length() / settings.speed;
+            // Once the phase is changing, a walk lasts only until everyone
+            // waiting for it has stepped off.
+            let walk_time = if self.pending.is_some() && !waiting(Route::Leg(leg)) {
+                0.0
+            } else {
+                walk_time
+            };
+            self.walk_signals[leg.index()].update(
+                calls[leg.index()],
+                crossing(Route::Leg(leg)),
+                walk_time,
+                clearance as f64,
+                now,
+            );
+        }
+        // From the waiting spot to the kerb at the far corner.
+        let (x, y) = self.scenario.waiting_spot(Direction::North);
+        let (kx, ky) = self.scenario.box_corner(Direction::South);
+        let clearance = (kx - x).hypot(ky - y) / settings.speed;
+        self.diagonal_signal.update(
+            diagonal,
+            crossing(Route::Diagonal),
+            walk_time,
+            clearance as f64,
+            now,
+        );
+    }
+
+    // Sets off those waiting at a crosswalk showing walk, unless a vehicle
+    // is already on its way over it, walks everyone crossing on, and takes
+    // off those who have reached the far side. Crossing diagonally needs
+    // the whole intersection clear and no vehicle anywhere on the way, and
+    // someone crossing diagonally stops short of a vehicle's body rather
+    // than walk into it. At an all-way stop a vehicle whose turn it is also
+    // goes before anyone who got to the kerb after it stopped.
+    fn move_pedestrians(&mut self, dt: f32) {
+        let now = self.time;
+        let occupied =
+            Direction::ALL.map(|leg| self.cars.iter().any(|car| self.drives_over(car, leg)));
+        let bodies: Vec<_> = self.cars.iter().map(Car::corners).collect();
+        let in_the_way =
+            |footprint: [(f32, f32); 4]| bodies.iter().any(|body| overlaps(body, &footprint));
+        let turns = match self.scenario.control {
+            Control::Signals => Vec::new(),
+            Control::AllWayStop => self.stop_sign_turns(),
+        };
+        let first_stopped = Direction::ALL.map(|leg| {
+            self.cars
+                .iter()
+                .filter(|car| turns.contains(&car.id) && car.crosswalk_legs().contains(&Some(leg)))
+                .filter_map(|car| car.arrived)
+                .fold(f64::INFINITY, f64::min)
+        });
+        for pedestrian in &mut self.pedestrians {
+            if !pedestrian.is_crossing() {
+                let signal = match pedestrian.route {
+                    Route::Leg(leg) => &self.walk_signals[leg.index()],
+                    Route::Diagonal => &self.diagonal_signal,
+                };
+                if signal.state != WalkState::Walk {
+                    continue;
+                }
+                let blocked = match pedestrian.route {
+                    Route::Leg(leg) => {
+                        occupied[leg.index()] || first_stopped[leg.index()] < pedestrian.spawned_at
+                    }
+                    // A diagonal crosses the whole box, so it waits for that to clear too.
+                    Route::Diagonal => {
+                        occupied.contains(&true)
+                            || !self.zone.is_empty()
+                            || pedestrian.way_ahead().any(in_the_way)
+                    }
+                };
+                if blocked {
+                    continue;
+                }
+                pedestrian.started_at = Some(now);
+            }
+            let step = pedestrian.speed * dt;
+            if pedestrian.route == Route::Diagonal
+                && in_the_way(pedestrian.corners_at(pedestrian.walked + step))
+            {
+                continue;
+            }
+            pedestrian.walk(step);
+        }
+
+        let stats = &mut self.stats;
+        self.pedestrians.retain(|pedestrian| {
+            if !pedestrian.has_crossed() {
+                return true;
+            }
+            match pedestrian.route {
+                Route::Leg(leg) => stats.pedestrians_crossed[leg.index()] += 1,
+                Route::Diagonal => stats.diagonals_crossed += 1,
+            }
+            let started = pedestrian.started_at.unwrap_or(now);
+            stats
+                .pedestrian_delays
+                .push(started - pedestrian.spawned_at);
+            false
+        });
+    }
+
+    // Whether a vehicle is on the crosswalk over `leg`, or past its stop
+    // line on its way over it. A cyclist waiting in a queue box is only in
+    // the way where its body is on the crosswalk.
+    fn drives_over(&self, car: &Car, leg: Direction) -> bool {
+        let [from, to] = car.crosswalk_legs().map(|crossed| crossed == Some(leg));
+        let front = car.along() + car.length / 2.0;
+        let under_way = car.check || car.heading == car.direction && front > self.stop_line(car);
+        if !car.queued && (!from && !to || !under_way) {
+            return false;
+        }
+        let crosswalk = self.scenario.crosswalk(leg);
+        let corners = car.corners();
+        let span = |measure: fn(Direction, f32, f32) -> f32| {
+            corners
+                .iter()
+                .map(|&(x, y)| measure(leg, x, y))
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), a| {
+                    (lo.min(a), hi.max(a))
+                })
+        };
+        let (lo, hi) = span(Direction::along);
+        if car.queued {
+            let (left, right) = span(Direction::lateral);
+            return lo < crosswalk.far
+                && hi > crosswalk.near
+                && left < crosswalk.end
+                && right > crosswalk.start;
+        }
+        // Arriving from the leg it has crossed once wholly in the box;
+        // leaving by it, once wholly beyond.
+        from && hi > crosswalk.near || to && lo < crosswalk.far
+    }
+
+    // Whether a car at its line has to let someone on the crosswalk it
+    // drives over first get past its lane, or someone about to step onto
+    // either crosswalk on its way on a walk; at an all-way stop only someone
+    // who was there before the car stopped. Nobody drives in while anyone
+    // may be crossing diagonally. For someone already crossing where it
+    // leaves it pulls up short of that crosswalk instead.
+    fn gives_way_on_foot(&self, car: &Car) -> bool {
+        if self.diagonal_signal.state != WalkState::DontWalk
+            || self
+                .pedestrians
+                .iter()
+                .any(|p| p.route == Route::Diagonal && p.is_crossing())
+        {
+            return true;
+        }
+        let [entry, exit] = car.crosswalk_legs();
+        let exit_lane = self.scenario.exit_lane(car.direction, car.lane, car.turn);
+        let entry = entry.map(|leg| (leg, self.scenario.lane_point(car.direction, car.lane), true));
+        let exit = exit.map(|leg| (leg, self.scenario.lane_point(leg, exit_lane), false));
+        [entry, exit]
+            .into_iter()
+            .flatten()
+            .any(|(leg, (x, y), crossing)| {
+                let lane = leg.lateral(x, y);
+                let walk = self.walk_signals[leg.index()].state == WalkState::Walk;
+                self.pedestrians.iter().any(|p| {
+                    let walk = walk
+                        && (self.scenario.control == Control::Signals
+                            || car.arrived.is_none_or(|arrived| p.spawned_at <= arrived));
+                    let gives_way = if p.is_crossing() { crossing } else { walk };
+                    p.route == Route::Leg(leg) && gives_way && self.yet_to_pass(p, leg, lane)
+                })
+            })
+    }
+
+    // How far a car may go before the crosswalk over its exit while someone
+    // on it has yet to get past its exit lane, measured along its path.
+    fn exit_crosswalk_room(&self, car: &Car) -> Option<f32> {
+        let [_, Some(leg)] = car.crosswalk_legs() else {
+            return None;
+        };
+        let near = self.scenario.crosswalk(leg).near;
+        let front = car.along() + car.length / 2.0;
+        // Angled across the turn, the outer front corner leads the middle
+        // of the front by up to half the width.
+        let corner = car.width / 2.0;
+        let room = match car.arc {
+            _ if car.heading != leg => {
+                // Still to turn: up to the turn, round it, then on to the
+                // crosswalk.
+                let arc = self.scenario.turn_arc(car.direction, car.lane, car.turn)?;
+                let (x, y) = arc.point(arc.length());
+                let to_turn = self.scenario.turn_start(car.direction, car.lane, car.turn) - front;
+                to_turn + arc.length() + near - leg.along(x, y) - corner
+            }
+            Some(arc) => {
+                let (x, y) = arc.point(car.on_arc);
+                near - leg.along(x, y) - corner
+            }
+            None => near - front,
+        };
+        let exit_lane = self.scenario.exit_lane(car.direction, car.lane, car.turn);
+        let (x, y) = self.scenario.lane_point(leg, exit_lane);
+        let lane = leg.lateral(x, y);
+        let crossing = self.pedestrians.iter().any(|p| {
+            p.route == Route::Leg(leg) && p.is_crossing() && self.yet_to_pass(p, leg, lane)
+        });
+        // Pulled up at the crosswalk it stays there; one already on it
+        // drives on.
+        (crossing && room > -STOPPED_AT_LINE).then_some(room.max(0.0))
+    }
+
+    // Whether a pedestrian on the crosswalk over `leg` is still short of, or
+    // not yet a lane width past, the middle of the lane at `lane` across it.
+    fn yet_to_pass(&self, pedestrian: &Pedestrian, leg: Direction, lane: f32) -> bool {
+        let (x, y) = pedestrian.position();
+        let across = leg.lateral(x, y);
+        let (from, to) = (pedestrian.from, pedestrian.to);
+        let ahead = if leg.lateral(to.0, to.1) > leg.lateral(from.0, from.1) {
+            lane - across
+        } else {
+            across - lane
+        };
+        ahead > -self.scenario.lane_width
+    }
+
+    // Moves vehicles on their approaches over a lane where MOBIL finds it
+    // pays, and sends any that reach the solid lines in a lane not marked
+    // for their turn on a movement it is marked for.
+    fn change_lanes(&mut self) {
+        for i in 0..self.cars.len() {
+            let car = &self.cars[i];
+            if car.check
+                || car.heading != car.direction
+                || car.lane_change.is_some()
+                || car.class == VehicleClass::Bicycle
+            {
+                continue;
+            }
+            let front = car.along() + car.length / 2.0;
+            let room = self.scenario.solid_lines(car.direction) - front;
+            if room <= 0.0 {
+                if !self.scenario.lanes(car.direction)[car.lane].allows(car.turn) {
+                    self.reroute(i);
+                }
+                continue;
+            }
+            let Some(lane) = self.better_lane(car, room) else {
+                continue;
+            };
+            let (x, y) = self.scenario.lane_point(car.heading, lane);
+            let offset = car.heading.lateral(x, y) - car.lateral();
+            let car = &mut self.cars[i];
+            car.change_lane(lane, offset, self.scenario.lane_change.distance);
+            car.path = ConflictZone::path(&self.scenario, car);
+            self.stats.lane_changes[car.direction.index()] += 1;
+        }
+    }
+
+    // The neighbouring lane MOBIL would move `car` into, if any, `room`
+    // before the solid lines. A change has to leave the vehicles ahead and
+    // behind in the new lane a safe gap and must not make the new follower
+    // brake harder than `safe_decel`; the best one gaining more than
+    // `threshold` is taken.
+    fn better_lane(&self, car: &Car, room: f32) -> Option<usize> {
+        // The whole body has to be across before the solid lines.
+        let params = &self.scenario.lane_change;
+        let needed = params.distance + car.length;
+        if room < needed {
+            return None;
+        }
+        let lanes = self.scenario.lanes(car.direction);
+        let bias = |lane: usize| {
+            if lanes[lane].allows(car.turn) {
+                0.0
+            } else {
+                -params.bias
+            }
+        };
+        let (leader, follower) = self.neighbours(car, car.lane);
+        let before = self.following(car, leader);
+
+        let mut best = None;
+        let mut best_gain = params.threshold;
+        for lane in [car.lane.wrapping_sub(1), car.lane + 1] {
+            if lane >= lanes.len() {
+                continue;
+            }
+            // Only leave the lanes marked for the turn with room to get back.
+            if !lanes[lane].allows(car.turn) && room < 2.0 * needed {
+                continue;
+            }
+            let (new_leader, new_follower) = self.neighbours(car, lane);
+            if !self.fits_between(car, new_leader, new_follower) {
+                continue;
+            }
+            let after = self.following(car, new_leader);
+            let mut others = 0.0;
+            if let Some(new_follower) = new_follower {
+                let braking = self.following(new_follower, Some(car));
+                if braking < -params.safe_decel {
+                    continue;
+                }
+                others += braking - self.following(new_follower, new_leader);
+            }
+            if let Some(follower) = follower {
+                others += self.following(follower, leader) - self.following(follower, Some(car));
+            }
+            let gain = after - before + params.politeness * others + bias(lane) - bias(car.lane);
+            if gain > best_gain {
+                best = Some(lane);
+                best_gain = gain;
+            }
+        }
+        best
+    }
+
+    // The nearest vehicles ahead of and behind `car` taking up any of
+    // `lane`.
+    fn neighbours(&self, car: &Car, lane: usize) -> (Option<&Car>, Option<&Car>) {
+        let (x, y) = self.scenario.lane_point(car.heading, lane);
+        let centre = car.heading.lateral(x, y);
+        let half_lane = self.scenario.lane_width / 2.0;
+        let along = car.along();
+        let mut ahead: Option<&Car> = None;
+        let mut behind: Option<&Car> = None;
+        for other in &self.cars {
+            if other.id == car.id || other.heading != car.heading {
+                continue;
+            }
+            let (lo, hi) = self.band(other);
+            if hi <= centre - half_lane || lo >= centre + half_lane {
+                continue;
+            }
+            let other_along = other.along();
+            if other_along > along {
+                if ahead.is_none_or(|ahead| ahead.along() > other_along) {
+                    ahead = Some(other);
+                }
+            } else if behind.is_none_or(|behind| behind.along() < other_along) {
+                behind = Some(other);
+            }
+        }
+        (ahead, behind)
+    }
+
+    fn fits_between(&self, car: &Car, leader: Option<&Car>, follower: Option<&Car>) -> bool {
+        let gap = self.scenario.safe_gap;
+        let front = car.along() + car.length / 2.0;
+        let rear = car.along() - car.length / 2.0;
+        leader.is_none_or(|leader| leader.along() - leader.length / 2.0 - front >= gap)
+            && follower
+                .is_none_or(|follower| rear - (follower.along() + follower.length / 2.0) >= gap)
+    }
+
+    // IDM acceleration of `car` behind `leader`, or on a clear road.
+    fn following(&self, car: &Car, leader: Option<&Car>) -> f32 {
+        car.acceleration(leader.map(|leader| {
+            let room = leader.along()
+                - leader.length / 2.0
+                - (car.along() + car.length / 2.0)
+                - self.scenario.safe_gap;
+            (room + CREEP, car.speed - leader.speed)
+        }))
+    }
+
+    // Lets a vehicle that missed the lanes for its turn go whichever way its
+    // lane is marked for, straight on where it can.
+    fn reroute(&mut self, i: usize) {
+        let car = &self.cars[i];
+        let lane = self.scenario.lanes(car.direction)[car.lane];
+        let turn = [Turn::Front, Turn::Left, Turn::Right]
+            .into_iter()
+            .find(|&turn| lane.allows(turn))
+            .unwrap_or(car.turn);
+        let (col, _) = COLORS
+            .into_iter()
+            .find(|&(_, t)| t == turn)
+            .unwrap_or((car.col, turn));
+        let car = &mut self.cars[i];
+        car.turn = turn;
+        car.col = col;
+        car.path = ConflictZone::path(&self.scenario, car);
+        self.stats.rerouted[car.direction.index()] += 1;
+    }
+
+    // How far across its heading a vehicle reaches: its body, the whole of
+    // it while it swings round a turn, and while it changes lanes the whole
+    // of the lane it is moving into as well.
+    fn band(&self, car: &Car) -> (f32, f32) {
+        let lateral = car.lateral();
+        let half = car.width / 2.0;
+        if car.arc.is_some() {
+            return car
+                .corners()
+                .iter()
+                .map(|&(x, y)| car.heading.lateral(x, y))
+                .fold((lateral - half, lateral + half), |(lo, hi), l| {
+                    (lo.min(l), hi.max(l))
+                });
+        }
+        if car.lane_change.is_none() {
+            return (lateral - half, lateral + half);
+        }
+        let (x, y) = self.scenario.lane_point(car.heading, car.lane);
+        let lane = car.heading.lateral(x, y);
+        car.corners()
+            .iter()
+            .map(|&(x, y)| car.heading.lateral(x, y))
+            .fold((lane - half, lane + half), |(lo, hi), l| {
+                (lo.min(l), hi.max(l))
+            })
+    }
+
+    // Whether two vehicles with the same heading are across each other's
+    // way, so one has to follow the other.
+    fn shares_lane(&self, a: &Car, b: &Car) -> bool {
+        let (a_lo, a_hi) = self.band(a);
+        let (b_lo, b_hi) = self.band(b);
+        a_lo < b_hi && b_lo < a_hi
+    }
+
+    fn turn_cars(&mut self) {
+        let waiting: Vec<bool> = self
+            .cars
+            .iter()
+            .map(|car| self.waits_to_turn(car))
+            .collect();
+        let scenario = &self.scenario;
+        for (car, waiting) in self.cars.iter_mut().zip(waiting) {
+            // A two-stage left that has pulled into its queue box turns to
+            // face across.
+            let pulling_in = car.check && car.heading == car.direction.right();
+            if car.two_stage() && pulling_in && !car.queued && car.arc.is_none() {
+                let spot = scenario.queue_box(car.direction);
+                if car.heading.along(spot.0, spot.1) - car.along() <= STOPPED_AT_LINE {
+                    car.queue(spot);
+                    car.path = ConflictZone::path(scenario, car);
+                }
+            }
+            if car.check {
+                continue;
+            }
+
+            // Turn once the front has gone past the start of the turn during
+            // the step, carrying the overshoot onto the turning path. One
+            // standing on it, as a right turner does at its line, waits.
+            let front = car.along() + car.length / 2.0;
+            let overshoot = front - scenario.turn_start(car.direction, car.lane, car.turn);
+            if overshoot <= 0.0 {
+                continue;
+            }
+            // Another car turning this step may have just taken the exit.
+            let (_, exit) = car.path;
+            if waiting || !self.zone.is_free(exit, car.id) {
+                car.advance(-overshoot);
+                continue;
+            }
+            self.zone.reserve(exit, car.id);
+
+            car.advance(-overshoot);
+            car.start_turn(scenario.turn_arc(car.direction, car.lane, car.turn));
+            car.advance(overshoot);
+
+            let count = &mut self.counts[car.direction.index()];
+            if *count > 0 {
+                car.check = true;
+                *count -= 1;
+                self.stats.departed[car.direction.index()] += 1;
+                self.stats.departed_by_class[car.class.index()] += 1;
+                self.stats.trips.push(self.time - car.spawned_at);
+                if car.class == VehicleClass::Emergency {
+                    self.stats.emergency_trips.push(self.time - car.spawned_at);
+                }
+            }
+        }
+    }
+
+    // The emergency vehicle to serve first, if any is still on its approach.
+    fn emergency(&self) -> Option<&Car> {
+        self.cars
+            .iter()
+            .find(|car| car.class == VehicleClass::Emergency && !car.check)
+    }
+
+    // Whether a car gives way to an emergency vehicle on another approach.
+    fn yields(&self, car: &Car) -> bool {
+        car.class != VehicleClass::Emergency
+            && self
+                .emergency()
+                .is_some_and(|vehicle| vehicle.direction != car.direction)
+    }
+
+    fn record_preemption(&mut self, dt: f32) {
+        if self.preempting.is_none() {
+            return;
+        }
+        self.stats.preempted_time += dt as f64;
+        for car in &self.cars {
+            if !car.check && !car.check_move && car.class != VehicleClass::Emergency {
+                self.stats.preemption_delay[car.direction.index()] += dt as f64;
+            }
+        }
+    }
+
+    // Notes when each vehicle comes to a full stop at its stop sign.
+    fn update_stop_signs(&mut self) {
+        let now = self.time;
+        let stopped: Vec<bool> = self
+            .cars
+            .iter()
+            .map(|car| {
+                let to_stop_line = self.stop_line(car) - (car.along() + car.length / 2.0);
+                !car.check
+                    && !car.check_move
+                    && car.heading == car.direction
+                    && (0.0..=STOPPED_AT_LINE).contains(&to_stop_line)
+            })
+            .collect();
+        for (car, stopped) in self.cars.iter_mut().zip(stopped) {
+            if stopped && car.arrived.is_none() {
+                car.arrived = Some(now);
+            }
+        }
+    }
+
+    // The vehicles whose turn it is to go at an all-way stop: those stopped
+    // at their lines that nobody else there goes before. First come, first
+    // served; of two arriving together the one on the other's right goes
+    // first, and of two facing each other the one not turning left. If
+    // everyone has someone to give way to, the first to arrive goes. While
+    // an emergency vehicle is coming, only those in front of it go.
+    fn stop_sign_turns(&self) -> Vec<u32> {
+        let emergency = self.emergency().map(|vehicle| vehicle.direction);
+        let waiting: Vec<(&Car, f64)> = self
+            .cars
+            .iter()
+            .filter(|car| {
+                !car.check
+                    && car.heading == car.direction
+                    && car.class != VehicleClass::Emergency
+                    && car.along() + car.length / 2.0 <= self.stop_line(car)
+            })
+            .filter(|car| emergency.is_none_or(|direction| car.direction == direction))
+            .filter_map(|car| Some((car, car.arrived?)))
+            .collect();
+        let goes_before = |(a, at): (&Car, f64), (b, bt): (&Car, f64)| {
+            if (at - bt).abs() > ARRIVAL_TIE {
+                return at < bt;
+            }
+            a.direction == b.direction.left()
+                || a.direction == b.direction.opposite()
+                    && b.turn == Turn::Left
+                    && a.turn != Turn::Left
+        };
+        let turns: Vec<u32> = waiting
+            .iter()
+            .filter(|&&b| !waiting.iter().any(|&a| goes_before(a, b)))
+            .map(|(car, _)| car.id)
+            .collect();
+        if !turns.is_empty() {
+            return turns;
+        }
+        waiting
+            .iter()
+            .min_by(|(a, at), (b, bt)| at.total_cmp(bt).then(a.id.cmp(&b.id)))
+            .map(|(car, _)| car.id)
+            .into_iter()
+            .collect()
+    }
+
+    // Whether a car may drive into the box now. A left turner that may have
+    // to wait for a gap pulls in as far as its turn with only those cells
+    // free, but not while another such turner holds any beyond, so two
+    // facing each other never end up waiting on each other's cells.
+    fn may_enter(&self, car: &Car) -> bool {
+        let (approach, exit) = car.path;
+        let free = if self.waits_for_gap(car) {
+            self.zone.is_free(approach, car.id)
+                && !self.zone.owners(exit).any(|id| {
+                    self.cars
+                        .iter()
+                        .any(|other| other.id == id && self.waits_for_gap(other))
+                })
+        } else {
+            self.zone.is_free(approach | exit, car.id)
+        };
+        free && !self.box_blocked(car)
+    }
+
+    // Whether a car is a left turner on its approach without a protected
+    // arrow, which may have to wait for a gap where its turn starts.
+    fn waits_for_gap(&self, car: &Car) -> bool {
+        !car.check
+            && car.heading == car.direction
+            && car.signal_turn() == Turn::Left
+            && self.signal(car.direction, Turn::Left) != LightState::Green
+    }
+
+    // Whether a car ahead in the same lane is in the box and may stand there,
+    // waiting to turn; following it in would block the box.
+    fn box_blocked(&self, car: &Car) -> bool {
+        if car.heading != car.direction {
+            return false;
+        }
+        let stop_line = self.stop_line(car);
+        let left_arrow = self.signal(car.direction, Turn::Left);
+        self.cars.iter().any(|other| {
+            other.direction == car.direction
+                && other.lane == car.lane
+                && other.heading == car.direction
+                && !other.check
+                && other.along() > car.along()
+                && other.along() + other.length / 2.0 > stop_line
+                && (!other.check_move
+                    || other.signal_turn() == Turn::Left && left_arrow != LightState::Green)
+        })
+    }
+
+    // Whether a right turner at its line would cut across a cyclist riding
+    // on alongside it, or about to draw level, in the bike lane.
+    fn cuts_across_cyclist(&self, car: &Car) -> bool {
+        if car.turn != Turn::Right || car.class == VehicleClass::Bicycle {
+            return false;
+        }
+        let rear = car.along() - car.length / 2.0;
+        self.cars.iter().any(|bike| {
+            bike.class == VehicleClass::Bicycle
+                && bike.check_move
+                && bike.heading == car.direction
+                && (!bike.check || bike.queued)
+                && bike.along() + bike.length / 2.0 > rear - bike.speed * self.scenario.critical_gap
+        })
+    }
+
+    // Whether a cyclist at its line may ride on as far as the queue boxes
+    // go: not while another is pulling into or waiting in the one on its
+    // approach, in its way, and on a two-stage left not until any other
+    // from the same approach is through the queue box it will use.
+    fn queue_box_clear(&self, bike: &Car) -> bool {
+        if bike.class != VehicleClass::Bicycle {
+            return true;
+        }
+        let approach = bike.direction.opposite();
+        !self.cars.iter().any(|other| {
+            if other.id == bike.id || !other.two_stage() {
+                return false;
+            }
+            let pulling_in = other.check && other.heading == other.direction.right();
+            let in_box = other.queued || pulling_in;
+            let ahead = other.direction.right() == approach && in_box;
+            let past_line =
+                !other.check && other.along() + other.length / 2.0 > self.stop_line(other);
+            let before =
+                bike.two_stage() && other.direction == bike.direction && (in_box || past_line);
+            ahead || before
+        })
+    }
+
+    // Position along its approach of the line a vehicle stops at for its
+    // signal; cyclists have their own.
+    fn stop_line(&self, car: &Car) -> f32 {
+        if car.class == VehicleClass::Bicycle {
+            self.scenario.bike_stop_line(car.direction)
+        } else {
+            self.scenario.stop_line(car.direction)
+        }
+    }
+
+    // Whether a car has to stop where its turn starts: to give way, or because
+    // the cells beyond it are still taken.
+    fn waits_to_turn(&self, car: &Car) -> bool {
+        if car.check || car.heading != car.direction {
+            return false;
+        }
+        let (_, exit) = car.path;
+        self.must_yield(car) || !self.zone.is_free(exit, car.id)
+    }
+
+    // Whether a left turner without a protected arrow has to wait for the
+    // oncoming through and right-turning traffic, which it crosses where its
+    // exit lane meets their lane. On a permissive arrow it needs a gap of at
+    // least `critical_gap` seconds; once the arrow has ended it only waits
+    // for cars already in the box, then clears.
+    fn must_yield(&self, car: &Car) -> bool {
+        if car.check || car.turn != Turn::Left || car.heading != car.direction {
+            return false;
+        }
+        let own = self.signal(car.direction, car.turn);
+        if own == LightState::Green {
+            return false;
+        }
+        let permissive = own == LightState::Flashing;
+
+        let oncoming = car.direction.opposite();
+        let exit = car.direction.left();
+        let lane = self.scenario.exit_lane(car.direction, car.lane, car.turn);
+        let (x, y) = self.scenario.lane_point(exit, lane);
+        let half_lane = self.scenario.lane_width / 2.0;
+        let conflict = oncoming.along(x, y);
+
+        self.cars.iter().any(|other| {
+            // Cars standing in the queue leave a gap until they set off.
+            if other.check
+                || !other.check_move
+                || other.direction != oncoming
+                || other.heading != oncoming
+                || other.signal_turn() == Turn::Left
+            {
+                return false;
+            }
+            let front = other.along() + other.length / 2.0;
+            let rear = other.along() - other.length / 2.0;
+            if rear > conflict + half_lane {
+                return false;
+            }
+            let in_box = front > self.stop_line(other);
+            let coming = matches!(
+                self.signal(oncoming, other.signal_turn()),
+                LightState::Green | LightState::Flashing | LightState::Amber
+            );
+            if !(in_box || permissive && coming) {
+                return false;
+            }
+            let distance = (conflict - half_lane - front).max(0.0);
+            distance / other.speed < self.scenario.critical_gap
+        })
+    }
+
+    // Right turn on red, where the approach allows it: a right turner that
+    // has come to a full stop at the line may go if nothing bound for its
+    // exit lane would reach the merge within `critical_gap` seconds.
+    fn may_turn_on_red(&self, car: &Car, to_stop_line: f32) -> bool {
+        if car.turn != Turn::Right
+            || car.check_move
+            || to_stop_line > STOPPED_AT_LINE
+            || !self.scenario.approach.get(car.direction).right_turn_on_red
+        {
+            return false;
+        }
+
+        let exit = car.direction.right();
+        let lane = self.scenario.exit_lane(car.direction, car.lane, car.turn);
+        let (x, y) = self.scenario.lane_point(car.direction, car.lane);
+        let merge = exit.along(x, y);
+        let half_lane = self.scenario.lane_width / 2.0;
+        !self.cars.iter().any(|other| {
+            let bound = if other.check {
+                other.heading
+            } else {
+                other.direction.turned(other.turn)
+            };
+            if bound != exit || !other.check_move || other.id == car.id {
+                return false;
+            }
+            // Only traffic joining the same exit lane is in the way.
+            let joins = if other.heading == exit {
+                let (lx, ly) = self.scenario.lane_point(exit, lane);
+                (other.lateral() - exit.lateral(lx, ly)).abs() < half_lane
+            } else {
+                self.scenario
+                    .exit_lane(other.direction, other.lane, other.turn)
+                    == lane
+            };
+            if !joins {
+                return false;
+            }
+            let distance = if other.heading == exit {
+                if other.along() - other.length / 2.0 > merge + half_lane {
+                    return false;
+                }
+                merge - half_lane - (other.along() + other.length / 2.0)
+            } else {
+                // Still on its approach: only a car with a signal to go, or
+                // already past its line, will come round.
+                let front = other.along() + other.length / 2.0;
+                let past_line = front > self.stop_line(other);
+                let go = matches!(
+                    self.signal(other.direction, other.signal_turn()),
+                    LightState::Green | LightState::Flashing | LightState::Amber
+                );
+                if !past_line && !go {
+                    return false;
+                }
+                self.scenario
+                    .turn_start(other.direction, other.lane, other.turn)
+                    - front
+            };
+            distance.max(0.0) / other.speed < self.scenario.critical_gap
+        })
+    }
+
+    // Reports every pair of cars that has started to overlap.
+    fn detect_collisions(&mut self) {
+        let mut colliding = Vec::new();
+        for (i, a) in self.cars.iter().enumerate() {
+            for b in &self.cars[i + 1..] {
+                if !overlaps(&a.corners(), &b.corners()) {
+                    continue;
+                }
+                let pair = [a.id.min(b.id), a.id.max(b.id)];
+                if !self.colliding.contains(&pair) {
+                    self.errors.push(SimulationError::Collision {
+                        time: self.time,
+                        cars: pair,
+                        x: (a.x + b.x) / 2.0,
+                        y: (a.y + b.y) / 2.0,
+                    });
+                }
+                colliding.push(pair);
+            }
+        }
+        self.colliding = colliding;
+    }
+
+    // Reports every vehicle that has run into a pedestrian.
+    fn detect_strikes(&mut self) {
+        let mut striking = Vec::new();
+        for pedestrian in &self.pedestrians {
+            let corners = pedestrian.corners();
+            for car in &self.cars {
+                if !overlaps(&car.corners(), &corners) {
+                    continue;
+                }
+                let pair = [car.id, pedestrian.id];
+                if !self.striking.contains(&pair) {
+                    let (x, y) = pedestrian.position();
+                    self.errors.push(SimulationError::PedestrianStruck {
+                        time: self.time,
+                        car: car.id,
+                        pedestrian: pedestrian.id,
+                        x,
+                        y,
+                    });
+                }
+                striking.push(pair);
+            }
+        }
+        self.striking = striking;
+    }
+
+    fn can_spawn(&self, direction: Direction, (x, y): (f32, f32), spec: &VehicleSpec) -> bool {
+        let along = direction.along(x, y);
+        let lateral = direction.lateral(x, y);
+        let half = spec.width / 2.0;
+        !self.cars.iter().any(|car| {
+            let follow = (car.length + spec.length) / 2.0 + self.scenario.safe_gap;
+            let (lo, hi) = self.band(car);
+            car.heading == direction
+                && lo < lateral + half
+                && lateral - half < hi
+                && (car.along() - along).abs() < follow
+        })
+    }
+
+    // What the signal shows vehicles travelling in `direction` for `turn`.
+    // At an all-way stop every movement counts as protected once a vehicle
+    // has its turn; the box's cells keep conflicting ones apart.
+    fn signal(&self, direction: Direction, turn: Turn) -> LightState {
+        match self.scenario.control {
+            Control::Signals => self.lights[direction.index()].state(turn),
+            Control::AllWayStop => LightState::Green,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenario::LaneUse;
+    use rand::RngCore;
+
+    fn car(sim: &Simulation, id: u32) -> Option<&Car> {
+        sim.cars.iter().find(|car| car.id == id)
+    }
+
+    fn past_line(sim: &Simulation, car: &Car) -> bool {
+        car.heading != car.direction || car.along() + car.length / 2.0 > sim.stop_line(car)
+    }
+
+    // Where the body of a car reaches along `leg`.
+    fn reach(car: &Car, leg: Direction) -> f32 {
+        car.corners()
+            .iter()
+            .map(|&(x, y)| leg.along(x, y))
+            .fold(f32::NEG_INFINITY, f32::max)
+    }
+
+    // Proposes the same phase whatever it sees.
+    struct Hold(Phase);
+
+    impl SignalController for Hold {
+        fn name(&self) -> &'static str {
+            "hold"
+        }
+
+        fn next_phase(&mut self, _view: &ControllerView, _rng: &mut dyn RngCore) -> Phase {
+            self.0
+        }
+    }
+
+    // A simulation whose signals stay on `phase`, with no approach ever
+    // forced green for having waited.
+    fn holding(mut scenario: Scenario, phase: Phase) -> Simulation {
+        let approaches = &mut scenario.approach;
+        for approach in [
+            &mut approaches.north,
+            &mut approaches.south,
+            &mut approaches.east,
+            &mut approaches.west,
+        ] {
+            approach.max_wait = 1000.0;
+        }
+        let mut sim = Simulation::new(scenario, 1);
+        sim.set_controller(Box::new(Hold(phase)));
+        sim
+    }
+
+    // Spawns a car for `turn` on `lane` of the approach travelling in
+    // `direction`.
+    fn spawn_in(sim: &mut Simulation, direction: Direction, lane: usize, turn: Turn) -> u32 {
+        assert!(sim.spawn_vehicle(direction, VehicleClass::Car, Col::Pink, turn));
+        let (x, y) = sim.scenario.spawn_point(VehicleClass::Car, direction, lane);
+        let car = sim.cars.last_mut().unwrap();
+        (car.lane, car.x, car.y) = (lane, x, y);
+        car.path = ConflictZone::path(&sim.scenario, car);
+        car.id
+    }
+
+    // Moves a car along its lane until its front is at `front`.
+    fn move_to(sim: &mut Simulation, id: u32, front: f32) {
+        let car = sim.cars.iter_mut().find(|car| car.id == id).unwrap();
+        let (vx, vy) = car.heading.vector();
+        let by = front - (car.along() + car.length / 2.0);
+        car.x += vx * by;
+        car.y += vy * by;
+    }
+
+    fn run_for(sim: &mut Simulation, seconds: f32) {
+        for _ in 0..(seconds / TICK) as usize {
+            sim.step(TICK);
+        }
+    }
+
+    #[test]
+    fn right_turner_waits_in_the_box_for_someone_crossing_its_exit() {
+        let mut scenario = Scenario::default();
+        scenario.pedestrians.enabled = true;
+        let mut sim = Simulation::new(scenario, 1);
+        assert!(sim.spawn_vehicle(Direction::North, VehicleClass::Car, Col::Pink, Turn::Right));
+        let id = sim.cars[0].id;
+        let leg = Direction::East;
+        let crosswalk = sim.scenario.crosswalk(leg);
+
+        // Once it is over its line, someone sets off over the crosswalk it
+        // turns onto, walking towards its exit lane.
+        while car(&sim, id).is_some_and(|c| !past_line(&sim, c)) {
+            sim.step(TICK);
+        }
+        let speed = sim.scenario.pedestrians.speed;
+        let mut pedestrian = Pedestrian::across(0, &crosswalk, leg.right(), 0.5, speed, sim.time);
+        pedestrian.started_at = Some(sim.time);
+        sim.pedestrians.push(pedestrian);
+
+        let on_road = |sim: &Simulation| {
+            sim.pedestrians.first().is_some_and(|p| {
+                let (x, y) = p.position();
+                leg.lateral(x, y) < crosswalk.end
+            })
+        };
+        let mut waited = false;
+        while on_road(&sim) {
+            sim.step(TICK);
+            let c = car(&sim, id).expect("drove off while someone was crossing");
+            assert!(reach(c, leg) <= crosswalk.near, "drove onto the crosswalk");
+            waited |= c.speed == 0.0 && past_line(&sim, c);
+        }
+        assert!(waited, "never stopped in the box for the pedestrian");
+
+        // With them across it goes.
+        for _ in 0..(5.0 / TICK) as usize {
+            sim.step(TICK);
+        }
+        assert!(car(&sim, id).is_none_or(|c| c.along() - c.length / 2.0 > crosswalk.far));
+        assert!(sim.errors.is_empty(), "{:?}", sim.errors);
+    }
+
+    #[test]
+    fn right_turner_goes_on_red_only_where_allowed() {
+        for allowed in [false, true] {
+            let mut scenario = Scenario::default();
+            scenario.approach.north.right_turn_on_red = allowed;
+            let mut sim = holding(scenario, Phase::ALL_RED);
+            let id = spawn_in(&mut sim, Direction::North, 0, Turn::Right);
+            run_for(&mut sim, 15.0);
+            let turned = car(&sim, id).is_none_or(|c| c.heading != c.direction);
+            assert_eq!(turned, allowed);
+            let on_red = sim.stats.right_turns_on_red[Direction::North.index()];
+            assert_eq!(on_red, allowed as u32);
+            assert!(sim.errors.is_empty(), "{:?}", sim.errors);
+        }
+    }
+
+    #[test]
+    fn right_turner_on_red_waits_for_traffic_bound_for_its_exit() {
+        let mut scenario = Scenario::default();
+        scenario.approach.north.right_turn_on_red = true;
+        let mut sim = holding(scenario, Phase::approach(Direction::East));
+        run_for(&mut sim, 3.0);
+        let id = spawn_in(&mut sim, Direction::North, 0, Turn::Right);
+        let line = sim.scenario.stop_line(Direction::North);
+        move_to(&mut sim, id, line);
+        sim.cars.last_mut().unwrap().check_move = false;
+        // Someone coming through on green, close by, into the lane it turns
+        // into.
+        let through = spawn_in(&mut sim, Direction::East, 0, Turn::Front);
+        let line = sim.scenario.stop_line(Direction::East);
+        move_to(&mut sim, through, line - 60.0);
+        while car(&sim, through).is_some_and(|c| !c.check) {
+            sim.step(TICK);
+            let c = car(&sim, id).unwrap();
+            assert_eq!(
+                c.heading, c.direction,
+                "turned in front of oncoming traffic"
+            );
+        }
+        run_for(&mut sim, 10.0);
+        assert_eq!(sim.stats.right_turns_on_red[Direction::North.index()], 1);
+        assert!(sim.errors.is_empty(), "{:?}", sim.errors);
+    }
+
+    #[test]
+    fn emergency_vehicle_gets_its_approach_within_the_clearance() {
+        let mut sim = holding(Scenario::default(), Phase::approach(Direction::East));
+        while sim.phase != Phase::approach(Direction::East) {
+            sim.step(TICK);
+        }
+        run_for(&mut sim, 3.0);
+        assert!(sim.spawn_emergency(Direction::North));
+        let asked = sim.time;
+        let signals = &sim.scenario.signals;
+        let clearance = (signals.amber_time + signals.all_red_time + signals.red_amber_time) as f64;
+        while sim.phase != Phase::approach(Direction::North) {
+            sim.step(TICK);
+            assert!(
+                sim.time - asked <= clearance + 2.0 * TICK as f64,
+                "still not served"
+            );
+        }
+        assert_eq!(sim.stats.preemptions, 1);
+        assert_eq!(
+            sim.lights[Direction::North.index()].state(Turn::Front),
+            LightState::Green
+        );
+    }
+
+    #[test]
+    fn car_changes_lanes_round_a_queue() {
+        let mut scenario = Scenario::default();
+        scenario.window.height = 1400.0;
+        scenario.approach.north.lanes = vec![LaneUse::LeftThrough, LaneUse::ThroughRight];
+        let mut sim = holding(scenario, Phase::ALL_RED);
+        let first = spawn_in(&mut sim, Direction::North, 0, Turn::Front);
+        run_for(&mut sim, 12.0);
+        assert!(
+            car(&sim, first).is_some_and(|c| c.speed == 0.0),
+            "not waiting at the line"
+        );
+
+        let second = spawn_in(&mut sim, Direction::North, 0, Turn::Front);
+        run_for(&mut sim, 12.0);
+        assert_eq!(car(&sim, second).unwrap().lane, 1);
+        assert_eq!(sim.stats.lane_changes[Direction::North.index()], 1);
+        assert!(sim.errors.is_empty(), "{:?}", sim.errors);
+    }
+
+    #[test]
+    fn car_in_the_wrong_lane_at_the_solid_lines_goes_the_way_it_is_marked() {
+        let mut scenario = Scenario::default();
+        scenario.approach.north.lanes = vec![LaneUse::Left, LaneUse::ThroughRight];
+        let mut sim = holding(scenario, Phase::ALL_RED);
+        let id = spawn_in(&mut sim, Direction::North, 1, Turn::Left);
+        // Too close to the solid lines to move over.
+        let front = sim.scenario.solid_lines(Direction::North) - 10.0;
+        move_to(&mut sim, id, front);
+        run_for(&mut sim, 2.0);
+        let c = car(&sim, id).unwrap();
+        assert_eq!((c.lane, c.turn), (1, Turn::Front));
+        assert_eq!(sim.stats.rerouted[Direction::North.index()], 1);
+        assert_eq!(sim.stats.lane_changes[Direction::North.index()], 0);
+    }
+
+    // Stands a car for `turn` at the stop line travelling in `direction`,
+    // having come to a stop there at `arrived`.
+    fn stopped_at_line(
+        sim: &mut Simulation,
+        direction: Direction,
+        turn: Turn,
+        arrived: f64,
+    ) -> u32 {
+        let id = spawn_in(sim, direction, 0, turn);
+        let line = sim.scenario.stop_line(direction);
+        move_to(sim, id, line);
+        let car = sim.cars.last_mut().unwrap();
+        car.speed = 0.0;
+        car.arrived = Some(arrived);
+        id
+    }
+
+    #[test]
+    fn all_way_stop_goes_first_come_first_served() {
+        let scenario = Scenario {
+            control: Control::AllWayStop,
+            ..Scenario::default()
+        };
+        let mut sim = Simulation::new(scenario, 1);
+        let first = stopped_at_line(&mut sim, Direction::North, Turn::Front, 1.0);
+        let later = stopped_at_line(&mut sim, Direction::North.left(), Turn::Front, 2.0);
+        assert_eq!(sim.stop_sign_turns(), vec![first]);
+
+        sim.cars.retain(|car| car.id != first);
+        assert_eq!(sim.stop_sign_turns(), vec![later]);
+    }
+
+    #[test]
+    fn all_way_stop_tie_goes_to_the_vehicle_on_the_right() {
+        let scenario = Scenario {
+            control: Control::AllWayStop,
+            ..Scenario::default()
+        };
+        let mut sim = Simulation::new(scenario, 1);
+        let left = stopped_at_line(&mut sim, Direction::North, Turn::Front, 1.0);
+        let right = stopped_at_line(&mut sim, Direction::North.left(), Turn::Front, 1.2);
+        assert_eq!(sim.stop_sign_turns(), vec![right]);
+
+        // Facing each other, the left turner gives way.
+        sim.cars.retain(|car| car.id != right);
+        let ahead = stopped_at_line(&mut sim, Direction::South, Turn::Left, 1.1);
+        assert_eq!(sim.stop_sign_turns(), vec![left]);
+        sim.cars.retain(|car| car.id != left);
+        assert_eq!(sim.stop_sign_turns(), vec![ahead]);
+    }
+}
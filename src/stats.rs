@@ -0,0 +1,39 @@
+/// Running totals for a simulation. Per-approach arrays are indexed by
+/// `Direction::index`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Statistics {
+    // Cars that entered on each approach.
+    pub spawned: [u32; 4],
+    // Cars that have made their movement and left their approach.
+    pub departed: [u32; 4],
+    // Right turners that went through a red after stopping.
+    pub right_turns_on_red: [u32; 4],
+    // Lane changes made on each approach, and vehicles that reached the
+    // solid lines in a lane not marked for their turn and made a movement
+    // it is marked for instead.
+    pub lane_changes: [u32; 4],
+    pub rerouted: [u32; 4],
+    // The same spawn and departure counts per `VehicleClass::index`.
+    pub spawned_by_class: [u32; 6],
+    pub departed_by_class: [u32; 6],
+    // Seconds each vehicle took from entering to leaving its approach.
+    pub trips: Vec<f64>,
+    // The same for emergency vehicles on their own.
+    pub emergency_trips: Vec<f64>,
+    // Emergency vehicles the signals were preempted for, and the seconds
+    // spent preempted.
+    pub preemptions: u32,
+    pub preempted_time: f64,
+    // Vehicle-seconds other traffic spent standing on each approach while
+    // the signals were preempted.
+    pub preemption_delay: [f64; 4],
+    // Pedestrians who arrived at and finished crossing each leg, and the
+    // seconds each one that crossed waited at the kerb.
+    pub pedestrians_spawned: [u32; 4],
+    pub pedestrians_crossed: [u32; 4],
+    pub pedestrian_delays: Vec<f64>,
+    // Those crossing diagonally in a scramble; their delays are counted
+    // with the rest.
+    pub diagonals_spawned: u32,
+    pub diagonals_crossed: u32,
+}
@@ -0,0 +1,166 @@
+use road_intersection::simulation::TICK;
+use road_intersection::{ControllerKind, Scenario, Simulation};
+
+// Steps a simulation the way the headless binary does, spawning a vehicle
+// every 30 ticks and pedestrians and cyclists every so often; 0 spawns none.
+fn run(
+    scenario: Scenario,
+    seed: u64,
+    ticks: u64,
+    pedestrian_every: u64,
+    bicycle_every: u64,
+) -> Simulation {
+    let mut sim = Simulation::new(scenario, seed);
+    for tick in 0..ticks {
+        if tick % 30 == 0 {
+            sim.spawn_random();
+        }
+        if pedestrian_every > 0 && tick % pedestrian_every == pedestrian_every / 3 {
+            sim.spawn_pedestrian_random();
+        }
+        if bicycle_every > 0 && tick % bicycle_every == bicycle_every * 2 / 3 {
+            sim.spawn_bicycle_random();
+        }
+        sim.step(TICK);
+    }
+    sim
+}
+
+fn parse(text: &str) -> Scenario {
+    let scenario: Scenario = toml::from_str(text).unwrap();
+    scenario.validate().unwrap();
+    scenario
+}
+
+#[test]
+fn default_scenario_runs_long_without_errors() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/scenarios/default.toml");
+    let sim = run(Scenario::load(path).unwrap(), 1, 20_000, 0, 0);
+    assert!(sim.errors.is_empty(), "{:?}", sim.errors);
+}
+
+// Several lanes each way, some turning on red, with crosswalks and bike
+// lanes ahead of advance stop boxes.
+const BUSY: &str = r#"
+[window]
+width = 1000.0
+height = 800.0
+
+[signals]
+plan = "leading-lefts"
+
+[approach.north]
+lanes = ["left", "left", "through", "right"]
+
+[approach.south]
+lanes = ["left", "through", "through", "through-right"]
+
+[approach.east]
+lanes = ["left-through", "through-right"]
+right_turn_on_red = true
+
+[approach.west]
+lanes = ["left", "through-right"]
+right_turn_on_red = true
+
+[pedestrians]
+enabled = true
+
+[bicycles]
+enabled = true
+advance_stop = 40.0
+"#;
+
+#[test]
+fn busy_scenario_runs_long_without_errors() {
+    let sim = run(parse(BUSY), 1, 20_000, 90, 120);
+    assert!(sim.errors.is_empty(), "{:?}", sim.errors);
+}
+
+#[test]
+fn busy_scenario_keeps_pedestrian_waits_within_max_wait() {
+    for controller in [
+        ControllerKind::MaxQueue,
+        ControllerKind::Actuated,
+        ControllerKind::RoundRobin,
+        ControllerKind::MaxPressure,
+    ] {
+        let mut scenario = parse(BUSY);
+        scenario.signals.controller = controller;
+        let max_wait = scenario.pedestrians.max_wait as f64;
+        let sim = run(scenario, 1, 10_000, 90, 120);
+        // Those still at the kerb count as well as those who have crossed.
+        let waiting = sim.pedestrians.iter().map(|p| match p.started_at {
+            Some(start) => start - p.spawned_at,
+            None => sim.time - p.spawned_at,
+        });
+        let worst = sim
+            .stats
+            .pedestrian_delays
+            .iter()
+            .copied()
+            .chain(waiting)
+            .fold(0.0, f64::max);
+        assert!(worst <= max_wait, "{controller:?}: waited {worst:.2}s");
+    }
+}
+
+// Approaches of different widths put the diagonals off square, across the
+// bike lanes just off the corners of the box where the queue boxes are.
+const UNEVEN_SCRAMBLE: &str = r#"
+[window]
+width = 1000.0
+height = 1000.0
+
+[approach.north]
+lanes = ["left", "through", "through-right"]
+
+[approach.south]
+lanes = ["left", "through-right"]
+
+[approach.east]
+lanes = ["left-through", "right"]
+
+[pedestrians]
+enabled = true
+
+[pedestrians.scramble]
+every = 3
+on_demand = true
+
+[bicycles]
+enabled = true
+advance_stop = 20.0
+
+[signals]
+plan = "leading-lefts"
+"#;
+
+#[test]
+fn queued_cyclists_keep_clear_of_uneven_diagonals() {
+    for controller in [ControllerKind::MaxQueue, ControllerKind::RoundRobin] {
+        let mut scenario = parse(UNEVEN_SCRAMBLE);
+        scenario.signals.controller = controller;
+        let sim = run(scenario, 7, 10_000, 150, 200);
+        assert!(sim.errors.is_empty(), "{controller:?}: {:?}", sim.errors);
+    }
+}
+
+// Ids and positions of the vehicles on the road.
+fn positions(sim: &Simulation) -> Vec<(u32, f32, f32)> {
+    sim.cars.iter().map(|car| (car.id, car.x, car.y)).collect()
+}
+
+#[test]
+fn same_seed_runs_the_same() {
+    let mut scenario = Scenario::default();
+    scenario.pedestrians.enabled = true;
+    scenario.bicycles.enabled = true;
+    let first = run(scenario.clone(), 42, 5_000, 90, 120);
+    let again = run(scenario.clone(), 42, 5_000, 90, 120);
+    assert_eq!(first.stats, again.stats);
+    assert_eq!(positions(&first), positions(&again));
+
+    let other = run(scenario, 43, 5_000, 90, 120);
+    assert_ne!(first.stats, other.stats);
+}